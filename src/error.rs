@@ -1,34 +1,178 @@
-use colored::Colorize;
+use crate::{model::Port, util::Paint as _};
+#[cfg(feature = "color")]
+use colored::control;
 use serde_yaml;
 use std::{
     error::Error,
     fmt,
     fmt::{Display, Formatter},
     io,
-    path::PathBuf,
+    path::{PathBuf, StripPrefixError},
+    sync::atomic::{AtomicU8, Ordering},
 };
+use toml::{de::Error as TomlDeError, ser::Error as TomlSerError};
 
-/// Wrapper around `serde_yaml::from_str`
+/// When error messages are colored. Only has an effect with the `color` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color only when stderr is a terminal and neither `NO_COLOR` nor `CLICOLOR=0` is set.
+    Auto,
+    Always,
+    Never,
+}
+
+static COLOR_CHOICE: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+/// Set process-wide when error messages are colored, defaults to [`ColorChoice::Auto`].
+pub fn set_color_choice(choice: ColorChoice) {
+    COLOR_CHOICE.store(choice as u8, Ordering::Relaxed);
+    #[cfg(feature = "color")]
+    match choice {
+        ColorChoice::Auto => control::unset_override(),
+        ColorChoice::Always => control::set_override(true),
+        ColorChoice::Never => control::set_override(false),
+    }
+}
+
+#[cfg(feature = "color")]
+pub(crate) fn should_color() -> bool {
+    match COLOR_CHOICE.load(Ordering::Relaxed) {
+        choice if choice == ColorChoice::Always as u8 => true,
+        choice if choice == ColorChoice::Never as u8 => false,
+        _ => io::IsTerminal::is_terminal(&io::stderr()),
+    }
+}
+
+/// Generate [`OrcaError`], its [`ErrorKind`] and a `From` conversion for every wrapped error.
+macro_rules! orca_error {
+    ($($(#[$meta:meta])* $variant:ident($error:ty),)*) => {
+        /// Any error raised by orcapod, wrapping either one of the structs below or a failure from
+        /// a dependency. It displays as the wrapped error and forwards its `source()`.
+        #[derive(Debug)]
+        pub enum OrcaError {
+            $($(#[$meta])* $variant($error),)*
+        }
+
+        /// Which [`OrcaError`] variant was raised, for matching without binding the error.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum ErrorKind {
+            $($(#[$meta])* $variant,)*
+        }
+
+        impl Display for ErrorKind {
+            fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+                match self {
+                    $($(#[$meta])* Self::$variant => write!(f, stringify!($variant)),)*
+                }
+            }
+        }
+
+        impl OrcaError {
+            pub const fn kind(&self) -> ErrorKind {
+                match self {
+                    $($(#[$meta])* Self::$variant(_) => ErrorKind::$variant,)*
+                }
+            }
+        }
+
+        impl Error for OrcaError {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                match self {
+                    $($(#[$meta])* Self::$variant(error) => error.source(),)*
+                }
+            }
+        }
+
+        impl Display for OrcaError {
+            fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+                match self {
+                    $($(#[$meta])* Self::$variant(error) => Display::fmt(error, f),)*
+                }
+            }
+        }
+
+        $(
+            $(#[$meta])*
+            impl From<$error> for OrcaError {
+                fn from(error: $error) -> Self {
+                    Self::$variant(error)
+                }
+            }
+        )*
+    };
+}
+
+impl OrcaError {
+    /// Render for people rather than logs: the error kind, message and every cause on its own
+    /// line.
+    pub const fn report(&self) -> Report<'_> {
+        Report { error: self }
+    }
+}
+
+/// Multi-line rendering of an [`OrcaError`] for human-facing CLIs, see [`OrcaError::report`].
 #[derive(Debug)]
-pub struct DeserializeFailure {
-    pub path: PathBuf,
-    pub error: serde_yaml::Error,
+pub struct Report<'error> {
+    error: &'error OrcaError,
 }
-impl Error for DeserializeFailure {}
-impl Display for DeserializeFailure {
+impl Display for Report<'_> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(
             f,
-            "{}{}{}{}",
-            "Failed to deserialize with error ".bright_red(),
-            self.error.to_string().bright_red(),
-            " for ".bright_red(),
-            self.path.to_string_lossy().bright_cyan()
-        )
+            "{} {}",
+            format!("error[{}]:", self.error.kind()).bright_red(),
+            self.error
+        )?;
+        let mut cause = self.error.source();
+        while let Some(found) = cause {
+            write!(f, "\n  {} {found}", "caused by:".cyan())?;
+            cause = found.source();
+        }
+        Ok(())
     }
 }
 
-/// Wrapper around getting None when trying to find parent
+orca_error! {
+    FileHasNoParent(FileHasNoParent),
+    IOFailure(IOFailure),
+    FileExists(FileExists),
+    NoAnnotationFound(NoAnnotationFound),
+    InvalidInputPacket(InvalidInputPacket),
+    InvalidOutputPacket(InvalidOutputPacket),
+    PodHashMismatch(PodHashMismatch),
+    TerminatedBeforeCreated(TerminatedBeforeCreated),
+    UnknownNode(UnknownNode),
+    UnknownStreamKey(UnknownStreamKey),
+    UnsatisfiedInput(UnsatisfiedInput),
+    DuplicateInput(DuplicateInput),
+    CyclicPipeline(CyclicPipeline),
+    AnnotationExists(AnnotationExists),
+    NoSpecFound(NoSpecFound),
+    AmbiguousHash(AmbiguousHash),
+    InvalidAnnotationName(InvalidAnnotationName),
+    LockPoisoned(LockPoisoned),
+    StillReferenced(StillReferenced),
+    UnknownModelClass(UnknownModelClass),
+    InvalidTag(InvalidTag),
+    InvalidHash(InvalidHash),
+    UnsupportedSchemaVersion(UnsupportedSchemaVersion),
+    InvalidDocument(InvalidDocument),
+    SpecHashMismatch(SpecHashMismatch),
+    InputOutput(io::Error),
+    Yaml(serde_yaml::Error),
+    Json(serde_json::Error),
+    TomlSerialize(TomlSerError),
+    TomlDeserialize(TomlDeError),
+    GlobPattern(glob::PatternError),
+    Glob(glob::GlobError),
+    Regex(regex::Error),
+    Semver(semver::Error),
+    StripPrefix(StripPrefixError),
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+}
+
+/// Wrapper around getting None when trying to find parent.
 #[derive(Debug)]
 pub struct FileHasNoParent {
     pub path: PathBuf,
@@ -44,33 +188,17 @@ impl Display for FileHasNoParent {
     }
 }
 
-/// Wrapper around `serde_yaml::to_string`
-#[derive(Debug)]
-pub struct SerializeFailure {
-    pub item_debug_string: String,
-    pub error: serde_yaml::Error,
-}
-impl Error for SerializeFailure {}
-impl Display for SerializeFailure {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}{}{}{}",
-            "Failed to seralize ".bright_red(),
-            self.item_debug_string.bright_cyan(),
-            " with error  ".bright_red(),
-            self.error.to_string().bright_red(),
-        )
-    }
-}
-
-/// Wrapper around `fs::read_to_string` and `fs::write`
+/// Wrapper around `fs::read_to_string` and `fs::write`.
 #[derive(Debug)]
 pub struct IOFailure {
     pub path: PathBuf,
     pub error: io::Error,
 }
-impl Error for IOFailure {}
+impl Error for IOFailure {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
 impl Display for IOFailure {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(
@@ -84,7 +212,7 @@ impl Display for IOFailure {
     }
 }
 
-/// Raise error when file exists but unexpected
+/// Raise error when file exists but unexpected.
 #[derive(Debug)]
 pub struct FileExists {
     pub path: PathBuf,
@@ -100,7 +228,7 @@ impl Display for FileExists {
     }
 }
 
-/// Raise error when glob doesn't match on an annotation
+/// Raise error when glob doesn't match on an annotation.
 #[derive(Debug)]
 pub struct NoAnnotationFound {
     pub class: String,
@@ -118,12 +246,382 @@ impl Display for NoAnnotationFound {
     }
 }
 
-/// Raise error when regex doesn't match
+/// Raise error when a job's input packet doesn't cover exactly the pod's input streams.
+#[derive(Debug)]
+pub struct InvalidInputPacket {
+    pub pod_hash: String,
+    pub missing: Vec<String>,
+    pub unexpected: Vec<String>,
+}
+impl Error for InvalidInputPacket {}
+impl Display for InvalidInputPacket {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Input packet for pod `{}` is missing keys [{}] and has unexpected keys [{}].",
+            self.pod_hash.bright_cyan(),
+            self.missing.join(", ").bright_red(),
+            self.unexpected.join(", ").bright_red(),
+        )
+    }
+}
+
+/// Raise error when a result's output packet doesn't match its pod's output streams.
+#[derive(Debug)]
+pub struct InvalidOutputPacket {
+    pub pod_hash: String,
+    pub missing: Vec<String>,
+    pub unexpected: Vec<String>,
+}
+impl Error for InvalidOutputPacket {}
+impl Display for InvalidOutputPacket {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Output packet for pod `{}` is missing keys [{}] and has unexpected keys [{}].",
+            self.pod_hash.bright_cyan(),
+            self.missing.join(", ").bright_red(),
+            self.unexpected.join(", ").bright_red(),
+        )
+    }
+}
+
+/// Raise error when a model references a different pod than the one provided.
+#[derive(Debug)]
+pub struct PodHashMismatch {
+    pub expected: String,
+    pub actual: String,
+}
+impl Error for PodHashMismatch {}
+impl Display for PodHashMismatch {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Expected pod `{}` but got pod `{}`.",
+            self.expected.bright_cyan(),
+            self.actual.bright_red(),
+        )
+    }
+}
+
+/// Raise error when a pod result claims to have terminated before it was created.
+#[derive(Debug)]
+pub struct TerminatedBeforeCreated {
+    pub created: u64,
+    pub terminated: u64,
+}
+impl Error for TerminatedBeforeCreated {}
+impl Display for TerminatedBeforeCreated {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Result terminated at `{}` before it was created at `{}`.",
+            self.terminated.to_string().bright_red(),
+            self.created.to_string().bright_cyan(),
+        )
+    }
+}
+
+/// Raise error when a pipeline refers to a node it doesn't declare.
+#[derive(Debug)]
+pub struct UnknownNode {
+    pub node: String,
+}
+impl Error for UnknownNode {}
+impl Display for UnknownNode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "Pipeline has no node `{}`.", self.node.bright_red())
+    }
+}
+
+/// Raise error when a pipeline port refers to a stream its pod doesn't have.
+#[derive(Debug)]
+pub struct UnknownStreamKey {
+    pub port: Port,
+}
+impl Error for UnknownStreamKey {}
+impl Display for UnknownStreamKey {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Node `{}` has no stream `{}`.",
+            self.port.node.bright_cyan(),
+            self.port.key.bright_red(),
+        )
+    }
+}
+
+/// Raise error when a pipeline leaves a node input unconnected.
+#[derive(Debug)]
+pub struct UnsatisfiedInput {
+    pub port: Port,
+}
+impl Error for UnsatisfiedInput {}
+impl Display for UnsatisfiedInput {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Input `{}` of node `{}` is not connected to an edge or pipeline input.",
+            self.port.key.bright_red(),
+            self.port.node.bright_cyan(),
+        )
+    }
+}
+
+/// Raise error when a pipeline feeds a node input more than once.
+#[derive(Debug)]
+pub struct DuplicateInput {
+    pub port: Port,
+}
+impl Error for DuplicateInput {}
+impl Display for DuplicateInput {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Input `{}` of node `{}` is connected more than once.",
+            self.port.key.bright_red(),
+            self.port.node.bright_cyan(),
+        )
+    }
+}
+
+/// Raise error when pipeline edges form a cycle.
+#[derive(Debug)]
+pub struct CyclicPipeline {
+    pub nodes: Vec<String>,
+}
+impl Error for CyclicPipeline {}
+impl Display for CyclicPipeline {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Pipeline has a cycle through nodes [{}].",
+            self.nodes.join(", ").bright_red(),
+        )
+    }
+}
+
+/// Raise error when an annotation is already taken in a store.
 #[derive(Debug)]
-pub struct NoRegexMatch;
-impl Error for NoRegexMatch {}
-impl Display for NoRegexMatch {
+pub struct AnnotationExists {
+    pub class: String,
+    pub name: String,
+    pub version: String,
+}
+impl Error for AnnotationExists {}
+impl Display for AnnotationExists {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Annotation `{}:{}` {} already exists.",
+            self.name.bright_red(),
+            self.version.bright_red(),
+            self.class
+        )
+    }
+}
+
+/// Raise error when no stored spec matches a hash (or hash prefix).
+#[derive(Debug)]
+pub struct NoSpecFound {
+    pub class: String,
+    pub hash: String,
+}
+impl Error for NoSpecFound {}
+impl Display for NoSpecFound {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "No {} spec found for hash `{}`.",
+            self.class,
+            self.hash.bright_red()
+        )
+    }
+}
+
+/// Raise error when a hash prefix matches more than one stored spec.
+#[derive(Debug)]
+pub struct AmbiguousHash {
+    pub class: String,
+    pub prefix: String,
+    pub matches: Vec<String>,
+}
+impl Error for AmbiguousHash {}
+impl Display for AmbiguousHash {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Hash prefix `{}` is ambiguous, matching {} specs: {}.",
+            self.prefix.bright_red(),
+            self.class,
+            self.matches.join(", ").bright_cyan()
+        )
+    }
+}
+
+/// Raise error when an annotation name breaks a naming rule.
+#[derive(Debug)]
+pub struct InvalidAnnotationName {
+    pub name: String,
+    pub rule: String,
+}
+impl Error for InvalidAnnotationName {}
+impl Display for InvalidAnnotationName {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "No match for regex.")
+        write!(
+            f,
+            "Annotation name `{}` is invalid: {}.",
+            self.name.bright_red(),
+            self.rule
+        )
+    }
+}
+
+/// Raise error when a lock is poisoned by a thread that panicked while holding it.
+#[derive(Debug)]
+pub struct LockPoisoned {
+    pub resource: String,
+}
+impl Error for LockPoisoned {}
+impl Display for LockPoisoned {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Lock on {} was poisoned by a panic.",
+            self.resource.bright_red()
+        )
+    }
+}
+
+/// Raise error when deleting a model that other stored models still reference.
+#[derive(Debug)]
+pub struct StillReferenced {
+    pub class: String,
+    pub name: String,
+    pub version: String,
+    /// `class name:version` of every referrer.
+    pub referrers: Vec<String>,
+}
+impl Error for StillReferenced {}
+impl Display for StillReferenced {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Can't delete {} `{}:{}` since it is referenced by: {}.",
+            self.class,
+            self.name.bright_red(),
+            self.version.bright_red(),
+            self.referrers.join(", ").bright_cyan()
+        )
+    }
+}
+
+/// Raise error when garbage collection finds models of classes it can't follow references of.
+#[derive(Debug)]
+pub struct UnknownModelClass {
+    pub classes: Vec<String>,
+}
+impl Error for UnknownModelClass {}
+impl Display for UnknownModelClass {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Can't collect garbage in a store holding models of unknown classes: {}.",
+            self.classes.join(", ").bright_red()
+        )
+    }
+}
+
+/// Raise error when a tag name breaks a naming rule.
+#[derive(Debug)]
+pub struct InvalidTag {
+    pub tag: String,
+    pub rule: String,
+}
+impl Error for InvalidTag {}
+impl Display for InvalidTag {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Tag `{}` is invalid: {}.",
+            self.tag.bright_red(),
+            self.rule
+        )
+    }
+}
+
+/// Raise error when a hash isn't a valid `<algorithm>:<digest>` identifier.
+#[derive(Debug)]
+pub struct InvalidHash {
+    pub hash: String,
+    pub reason: String,
+}
+impl Error for InvalidHash {}
+impl Display for InvalidHash {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Hash `{}` is invalid: {}.",
+            self.hash.bright_red(),
+            self.reason
+        )
+    }
+}
+
+/// Raise error when a spec's schema version can't be upgraded to the current one.
+#[derive(Debug)]
+pub struct UnsupportedSchemaVersion {
+    pub class: String,
+    pub version: u32,
+    pub current: u32,
+}
+impl Error for UnsupportedSchemaVersion {}
+impl Display for UnsupportedSchemaVersion {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "No migration from schema version {} to the current {} for {} specs.",
+            self.version.to_string().bright_red(),
+            self.current,
+            self.class
+        )
+    }
+}
+
+/// Raise error when a self-contained document can't be read as a model of the expected class.
+#[derive(Debug)]
+pub struct InvalidDocument {
+    pub class: String,
+    pub reason: String,
+}
+impl Error for InvalidDocument {}
+impl Display for InvalidDocument {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Document is not a valid {}: {}.",
+            self.class.bright_cyan(),
+            self.reason
+        )
+    }
+}
+
+/// Raise error when a document declares a hash that its content doesn't hash to.
+#[derive(Debug)]
+pub struct SpecHashMismatch {
+    pub class: String,
+    pub declared: String,
+    pub computed: String,
+}
+impl Error for SpecHashMismatch {}
+impl Display for SpecHashMismatch {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Document declares {} `{}` but its content hashes to `{}`.",
+            self.class,
+            self.declared.bright_red(),
+            self.computed.bright_cyan(),
+        )
     }
 }
@@ -1,47 +1,430 @@
-use crate::util::{get_type_name, hash};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use crate::{
+    error::{
+        CyclicPipeline, DuplicateInput, InvalidAnnotationName, InvalidDocument, InvalidHash,
+        InvalidInputPacket, InvalidOutputPacket, OrcaError, PodHashMismatch, SpecHashMismatch,
+        TerminatedBeforeCreated, UnknownNode, UnknownStreamKey, UnsatisfiedInput,
+        UnsupportedSchemaVersion,
+    },
+    util::{get_type_name, hash, normalize_path},
+};
+pub use semver::{Version, VersionReq};
+use serde::{de::DeserializeOwned, ser, Deserialize, Serialize, Serializer};
+use serde_json::{Number, Value as JsonValue};
 use serde_yaml::{Mapping, Value};
 use std::{
-    collections::BTreeMap,
-    error::Error,
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
     fs,
-    io::{BufRead, BufReader},
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
-pub fn to_yaml<T: Serialize>(instance: &T) -> Result<String, Box<dyn Error>> {
-    let mapping: BTreeMap<String, Value> = serde_yaml::from_str(&serde_yaml::to_string(instance)?)?; // sort
-    let mut yaml = serde_yaml::to_string(
-        &mapping
+/// Upgrades the body of a spec, its header stripped, from one schema version to the next, see
+/// `Model::migrations`.
+pub type Migration = fn(Mapping) -> Result<Mapping, OrcaError>;
+
+/// Schema version of specs written before the header recorded one.
+const UNVERSIONED_SCHEMA: u32 = 1;
+
+/// Text format of specs and documents. Hashes are computed from `to_canonical_json`, so a spec
+/// hashes the same whichever format it is written in.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpecFormat {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl SpecFormat {
+    /// File extension, without the dot.
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Yaml => "yaml",
+            Self::Json => "json",
+            Self::Toml => "toml",
+        }
+    }
+
+    /// Inverse of `extension`, `None` for files in no known format.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        [Self::Yaml, Self::Json, Self::Toml]
             .into_iter()
-            .filter(|(k, _)| k != "annotation" && k != "hash")
-            .collect::<BTreeMap<_, _>>(),
-    )?; // skip fields
-    yaml.insert_str(0, &format!("class: {}\n", get_type_name::<T>())); // replace class at top
+            .find(|format| format.extension() == extension)
+    }
+
+    /// Spec or document text of `mapping`, enum variants as single key mappings outside of YAML.
+    pub fn write(self, mapping: &Mapping) -> Result<String, OrcaError> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::to_string(mapping)?,
+            Self::Json => {
+                serde_json::to_string_pretty(&untag(Value::Mapping(mapping.clone())))? + "\n"
+            }
+            // TOML has no null, a missing optional field reads back as `None` all the same
+            Self::Toml => toml::to_string(&without_nulls(untag(Value::Mapping(mapping.clone()))))?,
+        })
+    }
+
+    /// Inverse of `write`, without any checks on the content.
+    pub fn read(self, text: &str) -> Result<Mapping, OrcaError> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::from_str(text)?,
+            Self::Json => serde_json::from_str(text)?,
+            Self::Toml => toml::from_str(text)?,
+        })
+    }
+}
+
+/// Write enum variants in `value` the way JSON does, as a mapping with the variant as its only key,
+/// instead of as YAML tags.
+fn untag(value: Value) -> Value {
+    match value {
+        Value::Tagged(tagged) => Value::Mapping(Mapping::from_iter([(
+            Value::from(tagged.tag.to_string().trim_start_matches('!')),
+            untag(tagged.value),
+        )])),
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .into_iter()
+                .map(|(key, field)| (key, untag(field)))
+                .collect(),
+        ),
+        Value::Sequence(items) => Value::Sequence(items.into_iter().map(untag).collect()),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) => value,
+    }
+}
+
+/// Drop every null field of the mappings in `value`.
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .into_iter()
+                .filter(|(_, field)| !field.is_null())
+                .map(|(key, field)| (key, without_nulls(field)))
+                .collect(),
+        ),
+        Value::Sequence(items) => Value::Sequence(items.into_iter().map(without_nulls).collect()),
+        Value::Null | Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Tagged(_) => {
+            value
+        }
+    }
+}
+
+/// Spec of `instance` as stored, a header with its class and schema version followed by its
+/// fields sorted by name, `annotation` and `hash` skipped.
+fn spec_mapping<T: Model>(instance: &T) -> Result<Mapping, OrcaError> {
+    let fields: BTreeMap<String, Value> = serde_yaml::from_str(&serde_yaml::to_string(instance)?)?; // sort
+    let mut mapping = Mapping::new();
+    mapping.insert(Value::from("class"), Value::from(T::class()));
+    mapping.insert(
+        Value::from("schema_version"),
+        Value::from(T::schema_version()),
+    );
+    mapping.extend(
+        fields
+            .into_iter()
+            .filter(|(name, _)| name != "annotation" && name != "hash")
+            .map(|(name, field)| (Value::from(name), field)),
+    ); // skip fields
+    Ok(mapping)
+}
+
+pub fn to_yaml<T: Model>(instance: &T) -> Result<String, OrcaError> {
+    to_spec(instance, SpecFormat::Yaml)
+}
+
+/// Same as `to_yaml` in any `SpecFormat`.
+pub fn to_spec<T: Model>(instance: &T, format: SpecFormat) -> Result<String, OrcaError> {
+    format.write(&spec_mapping(instance)?)
+}
+
+/// Encoding that spec hashes are computed from.
+///
+/// Hashes shouldn't depend on how a spec is presented in YAML, so this is compact JSON with
+/// sorted keys, the class added, `annotation` and `hash` skipped, and floats without a fractional
+/// part written as integers.
+pub fn to_canonical_json<T: Serialize>(instance: &T) -> Result<String, OrcaError> {
+    canonical_spec_json(serde_json::to_value(instance)?, &get_type_name::<T>())
+}
+
+/// Same as `to_canonical_json` but for a model of `class` that is already serialized.
+pub(crate) fn canonical_spec_json(mut value: JsonValue, class: &str) -> Result<String, OrcaError> {
+    if let JsonValue::Object(fields) = &mut value {
+        fields.remove("annotation");
+        fields.remove("hash");
+        fields.insert("class".to_owned(), JsonValue::from(class));
+    }
+    canonical_json(&value)
+}
 
-    Ok(yaml)
+fn canonical_json(value: &JsonValue) -> Result<String, OrcaError> {
+    Ok(match value {
+        JsonValue::Number(number) => canonical_number(number),
+        JsonValue::Array(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(canonical_json)
+                .collect::<Result<Vec<_>, _>>()?
+                .join(",")
+        ),
+        JsonValue::Object(fields) => format!(
+            "{{{}}}",
+            fields
+                .iter()
+                .collect::<BTreeMap<_, _>>()
+                .into_iter()
+                .map(|(key, field)| Ok(format!(
+                    "{}:{}",
+                    serde_json::to_string(key)?,
+                    canonical_json(field)?
+                )))
+                .collect::<Result<Vec<_>, OrcaError>>()?
+                .join(",")
+        ),
+        JsonValue::Null | JsonValue::Bool(_) | JsonValue::String(_) => {
+            serde_json::to_string(value)?
+        }
+    })
 }
 
-pub fn from_yaml<T: DeserializeOwned>(
+/// Floats are written in their shortest round-trip form, with `-0.0` written as `0`.
+fn canonical_number(number: &Number) -> String {
+    match number.as_f64() {
+        Some(float) if number.is_f64() && float == 0.0 => "0".to_owned(),
+        Some(float) if number.is_f64() && float.fract() == 0.0 => format!("{float:.0}"),
+        Some(_) | None => number.to_string(),
+    }
+}
+
+/// Serialize an `f32` as the `f64` of its shortest form, for `0.1_f32` to hash as `0.1` rather
+/// than the widened `0.10000000149011612`.
+#[expect(
+    clippy::min_ident_chars,
+    clippy::trivially_copy_pass_by_ref,
+    reason = "Signature is required by `serialize_with`."
+)]
+fn serialize_shortest_f32<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(value.to_string().parse().map_err(ser::Error::custom)?)
+}
+
+pub fn from_yaml<T: Model>(
     annotation_file: &Path,
     spec_file: &Path,
     hash: &str,
-) -> Result<T, Box<dyn Error>> {
-    let annotation: Mapping = serde_yaml::from_str(&fs::read_to_string(annotation_file)?)?;
-    let spec_yaml = BufReader::new(fs::File::open(spec_file)?)
-        .lines()
-        .skip(1)
-        .collect::<Result<Vec<_>, _>>()?
-        .join("\n");
-
-    let mut spec_mapping: BTreeMap<String, Value> = serde_yaml::from_str(&spec_yaml)?;
-    spec_mapping.insert("annotation".to_owned(), Value::from(annotation));
-    spec_mapping.insert("hash".to_owned(), Value::from(hash));
-
-    let instance: T = serde_yaml::from_str(&serde_yaml::to_string(&spec_mapping)?)?;
+) -> Result<T, OrcaError> {
+    from_yaml_str(
+        &fs::read_to_string(annotation_file)?,
+        &fs::read_to_string(spec_file)?,
+        hash,
+    )
+}
+
+/// Same as `from_yaml` but for annotation and spec contents that are already in memory. Specs at
+/// an older schema version are upgraded on the way.
+pub fn from_yaml_str<T: Model>(
+    annotation_yaml: &str,
+    spec_yaml: &str,
+    hash: &str,
+) -> Result<T, OrcaError> {
+    from_spec(annotation_yaml, spec_yaml, hash, SpecFormat::Yaml)
+}
+
+/// Same as `from_yaml_str` for a spec in any `SpecFormat`. Annotations are always YAML.
+pub fn from_spec<T: Model>(
+    annotation_yaml: &str,
+    spec: &str,
+    hash: &str,
+    format: SpecFormat,
+) -> Result<T, OrcaError> {
+    let annotation: Mapping = serde_yaml::from_str(annotation_yaml)?;
+    let mut spec_body = format.read(spec)?;
+    spec_body.remove("class");
+    let schema_version = spec_body
+        .remove("schema_version")
+        .map_or(Ok(UNVERSIONED_SCHEMA), serde_yaml::from_value)?;
+
+    let mut spec_mapping = upgrade_spec::<T>(spec_body, schema_version)?;
+    spec_mapping.insert(Value::from("annotation"), Value::from(annotation));
+    spec_mapping.insert(Value::from("hash"), Value::from(hash));
+
+    // through JSON since only YAML written specs tag their enum variants
+    let instance: T =
+        serde_json::from_value(serde_json::to_value(untag(Value::Mapping(spec_mapping)))?)?;
     Ok(instance)
 }
 
+/// Run the migrations of `T` that lead from `schema_version` to its current one.
+fn upgrade_spec<T: Model>(spec_body: Mapping, schema_version: u32) -> Result<Mapping, OrcaError> {
+    let unsupported = || UnsupportedSchemaVersion {
+        class: T::class(),
+        version: schema_version,
+        current: T::schema_version(),
+    };
+    if schema_version > T::schema_version() {
+        return Err(unsupported().into());
+    }
+    let migrations = T::migrations();
+    (schema_version..T::schema_version()).try_fold(spec_body, |upgraded, from_version| {
+        migrations.get(&from_version).ok_or_else(unsupported)?(upgraded)
+    })
+}
+
+/// Self-contained form of a model for passing it around outside of a store, its spec as `to_spec`
+/// writes it along with its annotation and hash. Read back with `Model::from_spec_str`.
+pub fn to_document<T: Model>(instance: &T, format: SpecFormat) -> Result<String, OrcaError> {
+    let mut document = spec_mapping(instance)?;
+    document.insert(
+        Value::from("annotation"),
+        serde_yaml::to_value(instance.annotation())?,
+    );
+    document.insert(Value::from("hash"), Value::from(instance.hash()));
+    format.write(&document)
+}
+
+/// `Model::from_document`, with the hash computed from the content rather than trusted.
+fn parse_document<T: Model, R: Resolver>(document: Value, resolver: &R) -> Result<T, OrcaError> {
+    let invalid = |reason: String| InvalidDocument {
+        class: T::class(),
+        reason,
+    };
+    let Value::Mapping(mut spec) = document else {
+        return Err(invalid("expected a mapping".to_owned()).into());
+    };
+    if let Some(class) = spec
+        .get("class")
+        .filter(|class| class.as_str() != Some(&T::class()))
+    {
+        return Err(invalid(format!(
+            "class is `{}`",
+            serde_yaml::to_string(class)?.trim()
+        ))
+        .into());
+    }
+    let annotation: Annotation = serde_yaml::from_value(
+        spec.remove("annotation")
+            .ok_or_else(|| invalid("missing `annotation`".to_owned()))?,
+    )?;
+    annotation.validate()?;
+    let declared = spec
+        .remove("hash")
+        .map(serde_yaml::from_value::<String>)
+        .transpose()?
+        .map(|declared_hash| declared_hash.parse::<Multihash>())
+        .transpose()?;
+
+    // spec upgraded to the current schema and normalized like its constructor would, hash still
+    // unknown
+    let unhashed = from_yaml_str::<T>(
+        &serde_yaml::to_string(&annotation)?,
+        &serde_yaml::to_string(&spec)?,
+        "",
+    )?
+    .rebuild(resolver)?;
+    let computed = Multihash::compute(
+        declared
+            .as_ref()
+            .map_or_else(HashAlgorithm::default, |multihash| multihash.algorithm),
+        &to_canonical_json(&unhashed)?,
+    );
+    if let Some(declared_hash) = declared.filter(|declared_hash| *declared_hash != computed) {
+        return Err(OrcaError::from(SpecHashMismatch {
+            class: T::class(),
+            declared: declared_hash.to_string(),
+            computed: computed.to_string(),
+        }));
+    }
+
+    with_hash(&unhashed, &computed)
+}
+
+/// Copy of `instance` carrying `hash`, which must have been computed from it.
+fn with_hash<T: Model>(instance: &T, hash: &Multihash) -> Result<T, OrcaError> {
+    let mut hashed = serde_json::to_value(instance)?;
+    if let JsonValue::Object(fields) = &mut hashed {
+        fields.insert("hash".to_owned(), JsonValue::from(hash.to_string()));
+    }
+    Ok(serde_json::from_value(hashed)?)
+}
+
+/// Looks up the models a document references, so that parsing can check it against them the way
+/// its constructor does. Every `Store` is one.
+pub trait Resolver {
+    /// `None` when the model is unknown here, which skips the checks that need it.
+    fn by_hash<T: Model>(&self, hash: &str) -> Result<Option<T>, OrcaError>;
+    /// `None` when the model is unknown here, which skips the checks that need it.
+    fn by_annotation<T: Model>(&self, name: &str, version: &str) -> Result<Option<T>, OrcaError>;
+}
+
+/// Resolves nothing, for documents parsed on their own.
+pub struct Unresolved;
+
+impl Resolver for Unresolved {
+    fn by_hash<T: Model>(&self, _hash: &str) -> Result<Option<T>, OrcaError> {
+        Ok(None)
+    }
+    fn by_annotation<T: Model>(&self, _name: &str, _version: &str) -> Result<Option<T>, OrcaError> {
+        Ok(None)
+    }
+}
+
+/// Shared interface of everything that can be hashed, annotated and kept in a `Store`.
+pub trait Model: Serialize + DeserializeOwned {
+    /// Class name used in specs and store layout, e.g. `pod` or `podjob`.
+    fn class() -> String {
+        get_type_name::<Self>()
+    }
+    /// Schema version written to the spec header. Bump it whenever the stored shape of the spec
+    /// changes, along with a migration from the previous version.
+    fn schema_version() -> u32 {
+        UNVERSIONED_SCHEMA
+    }
+    /// Upgrades from each older schema version to the next, keyed by the version upgraded from.
+    fn migrations() -> BTreeMap<u32, Migration> {
+        BTreeMap::new()
+    }
+    /// Parse a self-contained document as written by `to_document`. The hash is recomputed from
+    /// the content, and a document declaring a different one is rejected.
+    fn from_spec_str(document: &str, format: SpecFormat) -> Result<Self, OrcaError> {
+        Self::from_document(Value::Mapping(format.read(document)?))
+    }
+    /// Same as `from_spec_str` for a document that is already parsed, e.g. embedded in a config.
+    /// Checks that need a referenced model, like a job's input packet against its pod, are
+    /// skipped, see `from_document_with`.
+    fn from_document(document: Value) -> Result<Self, OrcaError> {
+        parse_document(document, &Unresolved)
+    }
+    /// Same as `from_document` but also checked against the models it references, looked up
+    /// with `resolver`, e.g. the store it is about to be saved to.
+    fn from_document_with<R: Resolver>(document: Value, resolver: &R) -> Result<Self, OrcaError> {
+        parse_document(document, resolver)
+    }
+    /// Run a parsed model through its constructor again, for it to be normalized and validated
+    /// the same as one built in code.
+    fn rebuild<R: Resolver>(self, _resolver: &R) -> Result<Self, OrcaError> {
+        Ok(self)
+    }
+    /// Same model hashed with `algorithm` instead, e.g. BLAKE3 for specs over large data.
+    fn with_hash_algorithm(&self, algorithm: HashAlgorithm) -> Result<Self, OrcaError> {
+        with_hash(
+            self,
+            &Multihash::compute(
+                algorithm,
+                &canonical_spec_json(serde_json::to_value(self)?, &Self::class())?,
+            ),
+        )
+    }
+    fn hash(&self) -> &str;
+    fn annotation(&self) -> &Annotation;
+    /// Every other model this one depends on, which stores must keep around for as long as
+    /// this one is.
+    fn references(&self) -> Vec<Reference> {
+        vec![]
+    }
+}
+
 // --- core model structs ---
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -54,11 +437,35 @@ pub struct Pod {
     input_stream_map: BTreeMap<String, StreamInfo>,
     output_dir: PathBuf,
     output_stream_map: BTreeMap<String, StreamInfo>,
+    #[serde(serialize_with = "serialize_shortest_f32")]
     recommended_cpus: f32,
     recommended_memory: u64,
     required_gpu: Option<GPURequirement>,
 }
 
+impl Model for Pod {
+    fn rebuild<R: Resolver>(self, _resolver: &R) -> Result<Self, OrcaError> {
+        Self::new(
+            self.annotation,
+            self.source_commit_url,
+            self.image,
+            self.command,
+            self.input_stream_map,
+            self.output_dir,
+            self.output_stream_map,
+            self.recommended_cpus,
+            self.recommended_memory,
+            self.required_gpu,
+        )
+    }
+    fn hash(&self) -> &str {
+        &self.hash
+    }
+    fn annotation(&self) -> &Annotation {
+        &self.annotation
+    }
+}
+
 impl Pod {
     pub fn new(
         annotation: Annotation,
@@ -71,36 +478,525 @@ impl Pod {
         recommended_cpus: f32,
         recommended_memory: u64,
         required_gpu: Option<GPURequirement>,
-    ) -> Result<Self, Box<dyn Error>> {
+    ) -> Result<Self, OrcaError> {
+        annotation.validate()?;
         let pod_no_hash = Self {
             annotation,
             hash: String::new(),
             source_commit_url,
             image,
             command,
-            input_stream_map,
-            output_dir,
-            output_stream_map,
+            // `/output/` and `/output` are the same directory so they should hash the same
+            input_stream_map: normalize_stream_paths(input_stream_map),
+            output_dir: normalize_path(output_dir),
+            output_stream_map: normalize_stream_paths(output_stream_map),
             recommended_cpus,
             recommended_memory,
             required_gpu,
         };
         Ok(Self {
-            hash: hash(&to_yaml::<Self>(&pod_no_hash)?),
+            hash: Multihash::of(&to_canonical_json::<Self>(&pod_no_hash)?).to_string(),
             ..pod_no_hash
         })
     }
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PodJob {
+    pub annotation: Annotation,
+    pub hash: String,
+    pod_hash: String,
+    input_packet: BTreeMap<String, Input>,
+    output_dir: PathBuf,
+}
+
+impl Model for PodJob {
+    fn rebuild<R: Resolver>(self, resolver: &R) -> Result<Self, OrcaError> {
+        match resolver.by_hash::<Pod>(&self.pod_hash)? {
+            Some(pod) => Self::new(self.annotation, &pod, self.input_packet, self.output_dir),
+            None => Self::build(
+                self.annotation,
+                self.pod_hash,
+                self.input_packet,
+                self.output_dir,
+            ),
+        }
+    }
+    fn hash(&self) -> &str {
+        &self.hash
+    }
+    fn annotation(&self) -> &Annotation {
+        &self.annotation
+    }
+    fn references(&self) -> Vec<Reference> {
+        vec![Reference::Hash {
+            class: Pod::class(),
+            hash: self.pod_hash.clone(),
+        }]
+    }
+}
+
+impl PodJob {
+    pub fn new(
+        annotation: Annotation,
+        pod: &Pod,
+        input_packet: BTreeMap<String, Input>,
+        output_dir: PathBuf,
+    ) -> Result<Self, OrcaError> {
+        let missing = pod
+            .input_stream_map
+            .keys()
+            .filter(|key| !input_packet.contains_key(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+        let unexpected = input_packet
+            .keys()
+            .filter(|key| !pod.input_stream_map.contains_key(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() || !unexpected.is_empty() {
+            return Err(OrcaError::from(InvalidInputPacket {
+                pod_hash: pod.hash.clone(),
+                missing,
+                unexpected,
+            }));
+        }
+        Self::build(annotation, pod.hash.clone(), input_packet, output_dir)
+    }
+
+    /// Everything `new` does short of checking the input packet against the pod.
+    fn build(
+        annotation: Annotation,
+        pod_hash: String,
+        input_packet: BTreeMap<String, Input>,
+        output_dir: PathBuf,
+    ) -> Result<Self, OrcaError> {
+        annotation.validate()?;
+        let pod_job_no_hash = Self {
+            annotation,
+            hash: String::new(),
+            pod_hash,
+            input_packet: normalize_input_packet(input_packet),
+            output_dir: normalize_path(output_dir),
+        };
+        Ok(Self {
+            hash: Multihash::of(&to_canonical_json::<Self>(&pod_job_no_hash)?).to_string(),
+            ..pod_job_no_hash
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PodResult {
+    pub annotation: Annotation,
+    pub hash: String,
+    pod_job_hash: String,
+    status: Status,
+    exit_code: i32,
+    created: u64,    // seconds since unix epoch
+    terminated: u64, // seconds since unix epoch
+    output_packet: BTreeMap<String, Output>,
+}
+
+impl Model for PodResult {
+    fn rebuild<R: Resolver>(self, resolver: &R) -> Result<Self, OrcaError> {
+        let Some(pod_job) = resolver.by_hash::<PodJob>(&self.pod_job_hash)? else {
+            return self.build();
+        };
+        let Some(pod) = resolver.by_hash::<Pod>(&pod_job.pod_hash)? else {
+            return self.build();
+        };
+        Self::new(
+            self.annotation,
+            &pod_job,
+            &pod,
+            self.status,
+            self.exit_code,
+            self.created,
+            self.terminated,
+            self.output_packet,
+        )
+    }
+    fn hash(&self) -> &str {
+        &self.hash
+    }
+    fn annotation(&self) -> &Annotation {
+        &self.annotation
+    }
+    fn references(&self) -> Vec<Reference> {
+        vec![Reference::Hash {
+            class: PodJob::class(),
+            hash: self.pod_job_hash.clone(),
+        }]
+    }
+}
+
+impl PodResult {
+    pub fn new(
+        annotation: Annotation,
+        pod_job: &PodJob,
+        pod: &Pod,
+        status: Status,
+        exit_code: i32,
+        created: u64,
+        terminated: u64,
+        output_packet: BTreeMap<String, Output>,
+    ) -> Result<Self, OrcaError> {
+        if pod_job.pod_hash != pod.hash {
+            return Err(OrcaError::from(PodHashMismatch {
+                expected: pod_job.pod_hash.clone(),
+                actual: pod.hash.clone(),
+            }));
+        }
+        // only a successful run is guaranteed to produce every output stream
+        let missing = match status {
+            Status::Succeeded => pod
+                .output_stream_map
+                .keys()
+                .filter(|key| !output_packet.contains_key(*key))
+                .cloned()
+                .collect::<Vec<_>>(),
+            Status::Failed | Status::Cancelled => vec![],
+        };
+        let unexpected = output_packet
+            .keys()
+            .filter(|key| !pod.output_stream_map.contains_key(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() || !unexpected.is_empty() {
+            return Err(OrcaError::from(InvalidOutputPacket {
+                pod_hash: pod.hash.clone(),
+                missing,
+                unexpected,
+            }));
+        }
+
+        Self {
+            annotation,
+            hash: String::new(),
+            pod_job_hash: pod_job.hash.clone(),
+            status,
+            exit_code,
+            created,
+            terminated,
+            output_packet,
+        }
+        .build()
+    }
+
+    /// Everything `new` does short of checking the output packet against the pod.
+    fn build(self) -> Result<Self, OrcaError> {
+        self.annotation.validate()?;
+        if self.terminated < self.created {
+            return Err(OrcaError::from(TerminatedBeforeCreated {
+                created: self.created,
+                terminated: self.terminated,
+            }));
+        }
+        let pod_result_no_hash = Self {
+            hash: String::new(),
+            output_packet: normalize_output_packet(self.output_packet),
+            ..self
+        };
+        Ok(Self {
+            hash: Multihash::of(&to_canonical_json::<Self>(&pod_result_no_hash)?).to_string(),
+            ..pod_result_no_hash
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Pipeline {
+    pub annotation: Annotation,
+    pub hash: String,
+    nodes: BTreeMap<String, PodRef>,
+    edges: Vec<Edge>,
+    input_map: BTreeMap<String, Vec<Port>>,
+}
+
+impl Model for Pipeline {
+    fn rebuild<R: Resolver>(self, resolver: &R) -> Result<Self, OrcaError> {
+        let pods = self
+            .nodes
+            .iter()
+            .map(|(node, pod_ref)| Ok(pod_ref.resolve(resolver)?.map(|pod| (node.clone(), pod))))
+            .collect::<Result<Vec<_>, OrcaError>>()?
+            .into_iter()
+            .collect::<Option<BTreeMap<_, _>>>();
+        Self::build(
+            self.annotation,
+            self.nodes,
+            self.edges,
+            self.input_map,
+            pods.as_ref(),
+        )
+    }
+    fn hash(&self) -> &str {
+        &self.hash
+    }
+    fn annotation(&self) -> &Annotation {
+        &self.annotation
+    }
+    fn references(&self) -> Vec<Reference> {
+        self.nodes
+            .values()
+            .map(|pod_ref| match pod_ref {
+                PodRef::Hash(hash) => Reference::Hash {
+                    class: Pod::class(),
+                    hash: hash.clone(),
+                },
+                PodRef::Annotation { name, version } => Reference::Annotation {
+                    class: Pod::class(),
+                    name: name.clone(),
+                    version: version.clone(),
+                },
+            })
+            .collect()
+    }
+}
+
+impl Pipeline {
+    /// Build a pipeline where `resolve` looks up the pod behind each node's reference so that
+    /// edges and inputs can be checked against the pods' stream maps.
+    pub fn new(
+        annotation: Annotation,
+        nodes: BTreeMap<String, PodRef>,
+        edges: Vec<Edge>,
+        input_map: BTreeMap<String, Vec<Port>>,
+        resolve: impl Fn(&PodRef) -> Result<Pod, OrcaError>,
+    ) -> Result<Self, OrcaError> {
+        let pods = nodes
+            .iter()
+            .map(|(node, pod_ref)| Ok((node.clone(), resolve(pod_ref)?)))
+            .collect::<Result<BTreeMap<_, _>, OrcaError>>()?;
+        Self::build(annotation, nodes, edges, input_map, Some(&pods))
+    }
+
+    /// Everything `new` does, where checks against the pods' stream maps are skipped without
+    /// `pods`.
+    fn build(
+        annotation: Annotation,
+        nodes: BTreeMap<String, PodRef>,
+        mut edges: Vec<Edge>,
+        mut input_map: BTreeMap<String, Vec<Port>>,
+        pods: Option<&BTreeMap<String, Pod>>,
+    ) -> Result<Self, OrcaError> {
+        annotation.validate()?;
+        // count how many times each node input is fed
+        let mut fed_inputs = BTreeMap::<&Port, usize>::new();
+        for edge in &edges {
+            Self::check_port(&nodes, pods, &edge.source, |pod| &pod.output_stream_map)?;
+            Self::check_port(&nodes, pods, &edge.target, |pod| &pod.input_stream_map)?;
+            *fed_inputs.entry(&edge.target).or_default() += 1;
+        }
+        for port in input_map.values().flatten() {
+            Self::check_port(&nodes, pods, port, |pod| &pod.input_stream_map)?;
+            *fed_inputs.entry(port).or_default() += 1;
+        }
+        if let Some(port) = pods
+            .into_iter()
+            .flatten()
+            .flat_map(|(node, pod)| {
+                pod.input_stream_map.keys().map(|key| Port {
+                    node: node.clone(),
+                    key: key.clone(),
+                })
+            })
+            .find(|port| !fed_inputs.contains_key(port))
+        {
+            return Err(OrcaError::from(UnsatisfiedInput { port }));
+        }
+        if let Some((port, _)) = fed_inputs.iter().find(|(_, count)| **count > 1) {
+            return Err(OrcaError::from(DuplicateInput {
+                port: (*port).clone(),
+            }));
+        }
+        Self::check_acyclic(&nodes, &edges)?;
+
+        // sort so that declaration order doesn't affect the hash
+        edges.sort();
+        for ports in input_map.values_mut() {
+            ports.sort();
+        }
+        let pipeline_no_hash = Self {
+            annotation,
+            hash: String::new(),
+            nodes,
+            edges,
+            input_map,
+        };
+        Ok(Self {
+            hash: Multihash::of(&to_canonical_json::<Self>(&pipeline_no_hash)?).to_string(),
+            ..pipeline_no_hash
+        })
+    }
+
+    fn check_port(
+        nodes: &BTreeMap<String, PodRef>,
+        pods: Option<&BTreeMap<String, Pod>>,
+        port: &Port,
+        stream_map: impl Fn(&Pod) -> &BTreeMap<String, StreamInfo>,
+    ) -> Result<(), OrcaError> {
+        if !nodes.contains_key(&port.node) {
+            return Err(OrcaError::from(UnknownNode {
+                node: port.node.clone(),
+            }));
+        }
+        if pods
+            .and_then(|found| found.get(&port.node))
+            .is_some_and(|pod| !stream_map(pod).contains_key(&port.key))
+        {
+            return Err(OrcaError::from(UnknownStreamKey { port: port.clone() }));
+        }
+        Ok(())
+    }
+
+    /// Kahn's algorithm: repeatedly drop nodes without incoming edges; anything left is a cycle.
+    fn check_acyclic(nodes: &BTreeMap<String, PodRef>, edges: &[Edge]) -> Result<(), OrcaError> {
+        let mut remaining = nodes.keys().collect::<BTreeSet<_>>();
+        while let Some(source) = remaining.iter().copied().find(|node| {
+            !edges
+                .iter()
+                .any(|edge| &&edge.target.node == node && remaining.contains(&edge.source.node))
+        }) {
+            remaining.remove(source);
+        }
+        if remaining.is_empty() {
+            Ok(())
+        } else {
+            Err(OrcaError::from(CyclicPipeline {
+                nodes: remaining.into_iter().cloned().collect(),
+            }))
+        }
+    }
+}
+
 // --- util types ---
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Annotation {
     pub name: String,
-    pub version: String,
+    pub version: Version,
     pub description: String,
 }
 
+impl Annotation {
+    /// Check the annotation upfront so that it can always be stored and listed back.
+    pub fn validate(&self) -> Result<(), OrcaError> {
+        Ok(Self::validate_name(&self.name)?)
+    }
+
+    /// Names must be non-empty and only use ASCII letters, digits and `-`.
+    pub fn validate_name(name: &str) -> Result<(), InvalidAnnotationName> {
+        let invalid = |rule: String| InvalidAnnotationName {
+            name: name.to_owned(),
+            rule,
+        };
+        if name.is_empty() {
+            return Err(invalid("must not be empty".to_owned()));
+        }
+        if let Some(found) = name
+            .chars()
+            .find(|character| !character.is_ascii_alphanumeric() && *character != '-')
+        {
+            return Err(invalid(format!(
+                "may only contain ASCII letters, digits and `-` but found `{found}`"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Algorithm behind a [`Multihash`]. BLAKE3 needs the `blake3` feature. Models are hashed with
+/// the default, SHA-256, unless rehashed with `Model::with_hash_algorithm`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Name of the algorithm as it prefixes a [`Multihash`].
+    pub const fn prefix(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            #[cfg(feature = "blake3")]
+            Self::Blake3 => "blake3",
+        }
+    }
+
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "sha256" => Some(Self::Sha256),
+            #[cfg(feature = "blake3")]
+            "blake3" => Some(Self::Blake3),
+            _ => None,
+        }
+    }
+}
+
+/// Self-describing spec hash `<algorithm>:<digest>` with an uppercase hex digest, e.g.
+/// `sha256:FEE2...`. This is what `Model::hash` returns and stores are laid out by.
+///
+/// A bare digest, as written before hashes were prefixed, parses as SHA-256.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Multihash {
+    pub algorithm: HashAlgorithm,
+    pub digest: String,
+}
+
+impl Multihash {
+    pub fn compute(algorithm: HashAlgorithm, buffer: &str) -> Self {
+        Self {
+            algorithm,
+            digest: hash(algorithm, buffer),
+        }
+    }
+
+    /// Hash with the default [`HashAlgorithm`].
+    pub fn of(buffer: &str) -> Self {
+        Self::compute(HashAlgorithm::default(), buffer)
+    }
+}
+
+impl Display for Multihash {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm.prefix(), self.digest)
+    }
+}
+
+impl FromStr for Multihash {
+    type Err = InvalidHash;
+
+    fn from_str(multihash: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| InvalidHash {
+            hash: multihash.to_owned(),
+            reason,
+        };
+        let (algorithm, digest) = match multihash.split_once(':') {
+            Some((prefix, digest)) => (
+                HashAlgorithm::from_prefix(prefix)
+                    .ok_or_else(|| invalid(format!("unknown algorithm `{prefix}`")))?,
+                digest,
+            ),
+            None => (HashAlgorithm::Sha256, multihash),
+        };
+        if digest.is_empty()
+            || !digest
+                .chars()
+                .all(|character| matches!(character, '0'..='9' | 'A'..='F'))
+        {
+            return Err(invalid("digest must be uppercase hex".to_owned()));
+        }
+        Ok(Self {
+            algorithm,
+            digest: digest.to_owned(),
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GPURequirement {
     pub model: GPUModel,
@@ -119,3 +1015,101 @@ pub struct StreamInfo {
     pub path: PathBuf,
     pub match_pattern: String,
 }
+
+fn normalize_stream_paths(
+    stream_map: BTreeMap<String, StreamInfo>,
+) -> BTreeMap<String, StreamInfo> {
+    stream_map
+        .into_iter()
+        .map(|(key, stream_info)| {
+            let path = normalize_path(&stream_info.path);
+            (
+                key,
+                StreamInfo {
+                    path,
+                    ..stream_info
+                },
+            )
+        })
+        .collect()
+}
+
+fn normalize_input_packet(input_packet: BTreeMap<String, Input>) -> BTreeMap<String, Input> {
+    input_packet
+        .into_iter()
+        .map(|(key, input)| match input {
+            Input::File(path) => (key, Input::File(normalize_path(path))),
+            Input::Blob(_) => (key, input),
+        })
+        .collect()
+}
+
+fn normalize_output_packet(output_packet: BTreeMap<String, Output>) -> BTreeMap<String, Output> {
+    output_packet
+        .into_iter()
+        .map(|(key, output)| {
+            let path = normalize_path(&output.path);
+            (key, Output { path, ..output })
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Input {
+    File(PathBuf),
+    Blob(String), // String will be the content hash of the stored blob
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Output {
+    pub path: PathBuf,
+    pub hash: String, // content hash of the produced file
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum PodRef {
+    Hash(String),
+    Annotation { name: String, version: String }, // version may be a requirement e.g. `^0.67`
+}
+
+impl PodRef {
+    fn resolve<R: Resolver>(&self, resolver: &R) -> Result<Option<Pod>, OrcaError> {
+        match self {
+            Self::Hash(hash) => resolver.by_hash(hash),
+            Self::Annotation { name, version } => resolver.by_annotation(name, version),
+        }
+    }
+}
+
+/// Link from one stored model to another, see [`Model::references`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reference {
+    Hash {
+        class: String,
+        hash: String,
+    },
+    Annotation {
+        class: String,
+        name: String,
+        version: String, // may be a requirement, same as `PodRef::Annotation`
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Port {
+    pub node: String,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+    pub source: Port, // output stream of the upstream node
+    pub target: Port, // input stream of the downstream node
+}
@@ -1,123 +1,949 @@
 use crate::{
-    error::{FileExists, FileHasNoParent, NoAnnotationFound, NoRegexMatch},
-    model::{from_yaml, to_yaml, Pod},
-    store::Store,
+    error::{
+        AnnotationExists, FileExists, FileHasNoParent, NoAnnotationFound, NoSpecFound, OrcaError,
+        UnknownModelClass,
+    },
+    model::{
+        from_spec, to_spec, Annotation, HashAlgorithm, Model, Pipeline, Pod, PodJob, PodResult,
+        Reference, SpecFormat,
+    },
+    store::{
+        hash_referrers, parse_spec, resolve_hash_prefix, verify_entries, ListFilter, Record, Store,
+        StoredEntry, TagMove, Unparsable, VerifyReport,
+    },
 };
-use colored::Colorize;
-use glob::{GlobError, Paths};
+use fs4::fs_std::FileExt as _;
+use glob::Pattern;
 use regex::Regex;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt as _;
 use std::{
-    collections::BTreeMap,
-    error::Error,
-    fs,
-    iter::Map,
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Write as _},
     path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
 };
+use tracing::{debug, info, instrument, warn};
+
+/// `(name, (hash, version))` parsed from an annotation path.
+type AnnotationMetadata = Result<(String, (String, String)), OrcaError>;
+
+/// `<hash>-<version>.yaml`, where the hash is a `Multihash` as written by [`encode_hash`] (bare in
+/// stores from before hashes were prefixed) and the version is semver.
+const ANNOTATION_FILE_PATTERN: &str = r"(?x)
+    ^
+    (?<hash>(?:[a-z][a-z0-9]*-)?[0-9A-F]+)
+    -
+    (?<version>
+        [0-9]+\.[0-9]+\.[0-9]+
+        (?:-[0-9A-Za-z\-\.]+)?
+        (?:\+[0-9A-Za-z\-\.]+)?
+    )
+    \.yaml
+    $";
+
+/// What an advisory lock in [`LocalFileStore`] serializes on.
+#[derive(Debug, Clone, Copy)]
+enum LockScope {
+    /// Every version under an annotation name.
+    Name,
+    /// A spec and the annotations pointing at it.
+    Hash,
+}
+
+/// Advisory lock held by this process, see [`LocalFileStore::lock`]. Released when dropped.
+#[derive(Debug)]
+struct StoreLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl StoreLock {
+    /// Release the lock and remove its file, once whatever it guards is gone. Waiters notice the
+    /// file was removed and take a fresh one.
+    fn remove(self) -> Result<(), OrcaError> {
+        fs::remove_file(&self.path)?;
+        debug!(path = %self.path.display(), "Removed lock.");
+        drop(self.file);
+        Ok(())
+    }
+}
+
+/// What [`LocalFileStore::save_file`] does when the file is already there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SaveMode {
+    /// Fail with `FileExists`.
+    CreateNew,
+    /// Keep the stored file, for content addressed by its hash.
+    KeepExisting,
+    /// Overwrite it.
+    Replace,
+}
+
+/// Reads the `(class, hash)` references out of a stored spec, see [`LocalFileStore::gc`].
+type ReferenceReader = fn(&LocalFileStore, &str) -> Result<Vec<(String, String)>, OrcaError>;
+
+/// Top level directories of the store that don't hold models of a class.
+const STORE_DIRECTORIES: [&str; 4] = ["annotation", "tag", "quarantine", ".lock"];
+
+/// An hour, enough for any save in flight to have written its annotation.
+const DEFAULT_GRACE_PERIOD_SECONDS: u64 = 3_600;
+
+/// Ten minutes, far longer than any single write takes, so older temporary files were left by an
+/// interrupted one rather than by a writer still in flight in another process.
+const STALE_TEMP_FILE_SECONDS: u64 = 600;
+
+/// Options for [`LocalFileStore::gc`]. The default is a dry run with an hour of grace.
+#[derive(Debug, Clone)]
+pub struct GcOptions {
+    /// Only report what would be removed.
+    pub dry_run: bool,
+    /// Unreachable specs modified more recently than this are kept, since a save writes the spec
+    /// before its annotation.
+    pub grace_period: Duration,
+}
+
+impl Default for GcOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: true,
+            grace_period: Duration::from_secs(DEFAULT_GRACE_PERIOD_SECONDS),
+        }
+    }
+}
+
+/// Spec directories found by [`LocalFileStore::gc`].
+#[derive(Debug, Default)]
+pub struct GcReport {
+    /// Every spec no annotation reaches, directly or through the specs it references.
+    pub unreachable: Vec<PathBuf>,
+    /// Unreachable but kept since they are within the grace period.
+    pub within_grace_period: Vec<PathBuf>,
+    /// Always empty on a dry run.
+    pub removed: Vec<PathBuf>,
+    /// Lock files left behind by specs and annotation names that no longer exist, always empty
+    /// on a dry run.
+    pub removed_locks: Vec<PathBuf>,
+}
+
+/// Keeps temporary file names unique across threads of the same process.
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Files cleaned up by [`LocalFileStore::recover`].
+#[derive(Debug, Default)]
+pub struct Recovery {
+    pub removed_temp_files: Vec<PathBuf>,
+    /// Where each annotation with a missing spec was moved to.
+    pub quarantined: Vec<PathBuf>,
+}
+
+/// Annotation file found by [`LocalFileStore::fsck`] that doesn't follow the store layout.
+#[derive(Debug)]
+pub struct NonConformingFile {
+    pub path: PathBuf,
+    pub reason: String,
+}
 
 #[derive(Debug)]
 pub struct LocalFileStore {
     pub directory: PathBuf,
+    /// Format new specs are written in, annotations and tags are always YAML. Specs already
+    /// stored are read in the format of their extension.
+    pub spec_format: SpecFormat,
+    /// See `Store::hash_algorithm`.
+    pub hash_algorithm: Option<HashAlgorithm>,
+}
+
+/// Hashes are laid out on disk as `<algorithm>-<digest>`, since `:` isn't allowed in file names
+/// on every platform.
+fn encode_hash(hash: &str) -> String {
+    hash.replacen(':', "-", 1)
+}
+
+/// Inverse of [`encode_hash`]. Bare digests from before hashes were prefixed have no `-`.
+fn decode_hash(encoded: &str) -> String {
+    encoded.replacen('-', ":", 1)
 }
 
 impl Store for LocalFileStore {
-    fn save_pod(&self, pod: &Pod) -> Result<(), Box<dyn Error>> {
-        let class = "pod";
+    fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        self.hash_algorithm
+    }
 
-        // Save the annotation file and throw and error if exist
+    #[instrument(skip_all, fields(class = T::class(), name = %model.annotation().name, version = %model.annotation().version))]
+    fn save_hashed<T: Model>(&self, model: &T) -> Result<(), OrcaError> {
+        let class = T::class();
+        let annotation = model.annotation();
+        annotation.validate()?;
+        let version = annotation.version.to_string();
+        let _name_lock = self.lock(LockScope::Name, &class, &annotation.name)?;
+
+        self.check_annotation_free(&class, &annotation.name, &version)?;
+
+        // Hold the hash lock until the annotation lands so a concurrent delete of another
+        // annotation can't remove the spec in between
+        let _hash_lock = self.lock(LockScope::Hash, &class, model.hash())?;
+        // and the ones of the specs it references, which `gc` checks before sweeping one
+        let _reference_locks = self.lock_references(model)?;
+        // Save the spec first so an annotation never points at a missing spec, and skip if it
+        // already exist in any format, for the case of many annotation to a single spec
+        let spec_file = self.stored_spec(&class, model.hash())?.map_or_else(
+            || self.make_spec_path(&class, model.hash()),
+            |(file, _)| file,
+        );
         Self::save_file(
-            &self.make_annotation_path(
-                class,
-                &pod.hash,
-                &pod.annotation.name,
-                &pod.annotation.version,
-            ),
-            &serde_yaml::to_string(&pod.annotation)?,
-            true,
+            &spec_file,
+            &to_spec::<T>(model, self.spec_format)?,
+            SaveMode::KeepExisting,
         )?;
 
-        // Save the pod and skip if it already exist, for the case of many annotation to a single pod
+        // Save the annotation file and throw and error if exist
         Self::save_file(
-            &self.make_spec_path(class, &pod.hash),
-            &to_yaml::<Pod>(pod)?,
-            false,
+            &self.make_annotation_path(&class, model.hash(), &annotation.name, &version),
+            &serde_yaml::to_string(annotation)?,
+            SaveMode::CreateNew,
         )?;
 
         Ok(())
     }
 
-    fn load_pod(&self, name: &str, version: &str) -> Result<Pod, Box<dyn Error>> {
-        let class = "pod".to_owned();
+    #[instrument(skip(self), fields(class = T::class()))]
+    fn load<T: Model>(&self, name: &str, version: &str) -> Result<T, OrcaError> {
+        let class = T::class();
+        let resolved = self.resolve_version::<T>(name, version)?.to_string();
 
         let (_, (hash, _)) =
-            Self::parse_annotation_path(&self.make_annotation_path("pod", "*", name, version))?
+            Self::parse_annotation_path(&self.make_annotation_path(&class, "*", name, &resolved))?
                 .next()
                 .ok_or_else(|| NoAnnotationFound {
-                    class,
+                    class: class.clone(),
                     name: name.to_owned(),
                     version: version.to_owned(),
                 })??;
 
-        from_yaml::<Pod>(
-            &self.make_annotation_path("pod", &hash, name, version),
-            &self.make_spec_path("pod", &hash),
+        self.read_model::<T>(
+            &self.make_annotation_path(&class, &hash, name, &resolved),
             &hash,
         )
     }
 
-    fn list_pod(&self) -> Result<BTreeMap<String, Vec<String>>, Box<dyn Error>> {
-        let (names, (hashes, versions)) =
-            Self::parse_annotation_path(&self.make_annotation_path("pod", "*", "*", "*"))?
-                .collect::<Result<(Vec<_>, (Vec<_>, Vec<_>)), _>>()?;
+    #[instrument(skip_all, fields(class = T::class()))]
+    fn list_iter<T: Model>(
+        &self,
+        filter: &ListFilter,
+    ) -> Result<impl Iterator<Item = Result<Record, OrcaError>> + '_, OrcaError> {
+        let class = T::class();
+        let record_filter = filter.clone();
+        // narrow the glob itself where possible and check the rest per record
+        let hash_glob = filter.hash_prefix.as_ref().map_or_else(
+            || "*".to_owned(),
+            |prefix| format!("*{}*", Pattern::escape(&encode_hash(prefix))),
+        );
+        let name_glob = filter.name.as_ref().map_or("*", |pattern| pattern.as_str());
+        let paths = Self::parse_annotation_path(
+            &self.make_annotation_path(&class, &hash_glob, name_glob, "*"),
+        )?;
 
-        Ok(BTreeMap::from([
-            (String::from("name"), names),
-            (String::from("hash"), hashes),
-            (String::from("version"), versions),
-        ]))
+        Ok(paths
+            .map(move |metadata| {
+                let (name, (hash, version)) = metadata?;
+                let annotation: Annotation = serde_yaml::from_str(&fs::read_to_string(
+                    self.make_annotation_path(&class, &hash, &name, &version),
+                )?)?;
+                Ok(Record {
+                    name,
+                    version: version.parse()?,
+                    hash,
+                    description: annotation.description,
+                })
+            })
+            .filter(move |record| {
+                record
+                    .as_ref()
+                    .map_or(true, |found| record_filter.matches(found))
+            }))
     }
 
-    fn delete_pod(&self, name: &str, version: &str) -> Result<(), Box<dyn Error>> {
-        // assumes propagate = false
-        let class = "pod".to_owned();
-        let versions = self.get_pod_version_map(name)?;
+    #[instrument(skip(self), fields(class = T::class()))]
+    fn delete_unchecked<T: Model>(&self, name: &str, version: &str) -> Result<(), OrcaError> {
+        let class = T::class();
+        let name_lock = self.lock(LockScope::Name, &class, name)?;
+        let versions = self.get_version_map(&class, name)?;
         let hash = versions.get(version).ok_or_else(|| NoAnnotationFound {
-            class,
+            class: class.clone(),
             name: name.to_owned(),
             version: version.to_owned(),
         })?;
 
-        let annotation_file = self.make_annotation_path("pod", hash, name, version);
+        let annotation_file = self.make_annotation_path(&class, hash, name, version);
         let annotation_dir = annotation_file.parent().ok_or_else(|| FileHasNoParent {
             path: annotation_file.clone(),
         })?;
-        let spec_file = self.make_spec_path("pod", hash);
+        let spec_file = self.make_spec_path(&class, hash);
         let spec_dir = spec_file.parent().ok_or_else(|| FileHasNoParent {
             path: spec_file.clone(),
         })?;
 
+        let hash_lock = self.lock(LockScope::Hash, &class, hash)?;
         fs::remove_file(&annotation_file)?;
-        if !versions
-            .iter()
-            .any(|(list_version, list_hash)| list_version != version && list_hash == hash)
+        info!(path = %annotation_file.display(), "Removed annotation.");
+        // the spec may still be referenced by annotations under other names
+        if Self::parse_annotation_path(&self.make_annotation_path(&class, hash, "*", "*"))?
+            .next()
+            .is_none()
         {
             fs::remove_dir_all(spec_dir)?;
+            info!(path = %spec_dir.display(), "Removed unreferenced spec.");
+            hash_lock.remove()?;
+        } else {
+            drop(hash_lock);
         }
         if !versions
             .iter()
             .any(|(list_version, _)| list_version != version)
         {
             fs::remove_dir_all(annotation_dir)?;
+            debug!(path = %annotation_dir.display(), "Removed empty annotation directory.");
+            name_lock.remove()?;
         }
 
         Ok(())
     }
+
+    #[instrument(skip(self, annotation), fields(class = T::class(), name = %annotation.name, version = %annotation.version))]
+    fn save_annotation<T: Model>(
+        &self,
+        hash: &str,
+        annotation: &Annotation,
+    ) -> Result<(), OrcaError> {
+        let class = T::class();
+        annotation.validate()?;
+        let version = annotation.version.to_string();
+        let _name_lock = self.lock(LockScope::Name, &class, &annotation.name)?;
+        self.check_annotation_free(&class, &annotation.name, &version)?;
+
+        // the hash lock keeps the spec from being deleted until the annotation lands
+        let _hash_lock = self.lock(LockScope::Hash, &class, hash)?;
+        if self.stored_spec(&class, hash)?.is_none() {
+            return Err(OrcaError::from(NoSpecFound {
+                class,
+                hash: hash.to_owned(),
+            }));
+        }
+        Self::save_file(
+            &self.make_annotation_path(&class, hash, &annotation.name, &version),
+            &serde_yaml::to_string(annotation)?,
+            SaveMode::CreateNew,
+        )
+    }
+
+    #[instrument(skip_all, fields(class = T::class(), name = %annotation.name, version = %annotation.version))]
+    fn replace_annotation<T: Model>(&self, annotation: &Annotation) -> Result<(), OrcaError> {
+        let class = T::class();
+        let version = annotation.version.to_string();
+        let _name_lock = self.lock(LockScope::Name, &class, &annotation.name)?;
+        let versions = self.get_version_map(&class, &annotation.name)?;
+        let hash = versions.get(&version).ok_or_else(|| NoAnnotationFound {
+            class: class.clone(),
+            name: annotation.name.clone(),
+            version: version.clone(),
+        })?;
+
+        Self::save_file(
+            &self.make_annotation_path(&class, hash, &annotation.name, &version),
+            &serde_yaml::to_string(annotation)?,
+            SaveMode::Replace,
+        )
+    }
+
+    #[instrument(skip_all, fields(class = T::class(), hash = model.hash()))]
+    fn replace_spec<T: Model>(&self, model: &T) -> Result<(), OrcaError> {
+        let class = T::class();
+        let _hash_lock = self.lock(LockScope::Hash, &class, model.hash())?;
+        // keep the format the spec was written in
+        let (spec_file, format) =
+            self.stored_spec(&class, model.hash())?
+                .ok_or_else(|| NoSpecFound {
+                    class,
+                    hash: model.hash().to_owned(),
+                })?;
+
+        Self::save_file(&spec_file, &to_spec::<T>(model, format)?, SaveMode::Replace)
+    }
+
+    #[instrument(skip(self, tag_move), fields(class = T::class()))]
+    fn append_tag_move<T: Model>(
+        &self,
+        name: &str,
+        tag: &str,
+        tag_move: &TagMove,
+    ) -> Result<(), OrcaError> {
+        let class = T::class();
+        let _name_lock = self.lock(LockScope::Name, &class, name)?;
+        let tag_file = self.make_tag_path(&class, name, tag);
+        let mut moves = if tag_file.exists() {
+            serde_yaml::from_str(&fs::read_to_string(&tag_file)?)?
+        } else {
+            vec![]
+        };
+        moves.push(tag_move.clone());
+
+        Self::save_file(
+            &tag_file,
+            &serde_yaml::to_string(&moves)?,
+            SaveMode::Replace,
+        )?;
+        info!(path = %tag_file.display(), version = ?tag_move.version, "Moved tag.");
+        Ok(())
+    }
+
+    #[instrument(skip(self), fields(class = T::class()))]
+    fn tag_log<T: Model>(&self, name: &str) -> Result<BTreeMap<String, Vec<TagMove>>, OrcaError> {
+        let tag_glob = self.make_tag_path(&T::class(), name, "*");
+        debug!(pattern = %tag_glob.display(), "Scanning tags.");
+        glob::glob(&tag_glob.to_string_lossy())?
+            .map(|filepath| {
+                let tag_file = filepath?;
+                let tag = tag_file
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string();
+                Ok((tag, serde_yaml::from_str(&fs::read_to_string(&tag_file)?)?))
+            })
+            .collect()
+    }
+
+    #[instrument(skip(self), fields(class = T::class()))]
+    fn load_by_hash<T: Model>(&self, hash_prefix: &str) -> Result<(T, Vec<Annotation>), OrcaError> {
+        let class = T::class();
+        let spec_glob = self
+            .directory
+            .join(&class)
+            .join(format!("*{}*", Pattern::escape(&encode_hash(hash_prefix))))
+            .join("spec.*");
+        debug!(pattern = %spec_glob.display(), "Scanning specs.");
+        let hash = resolve_hash_prefix(
+            &class,
+            hash_prefix,
+            glob::glob(&spec_glob.to_string_lossy())?
+                .map(|filepath| -> Result<String, OrcaError> {
+                    let spec_file = filepath?;
+                    Ok(decode_hash(
+                        &spec_file
+                            .parent()
+                            .and_then(Path::file_name)
+                            .ok_or_else(|| FileHasNoParent {
+                                path: spec_file.clone(),
+                            })?
+                            .to_string_lossy(),
+                    ))
+                })
+                .collect::<Result<BTreeSet<_>, _>>()?,
+        )?;
+
+        let mut annotation_files =
+            Self::parse_annotation_path(&self.make_annotation_path(&class, &hash, "*", "*"))?
+                .map(|metadata| {
+                    let (name, (_, version)) = metadata?;
+                    Ok(self.make_annotation_path(&class, &hash, &name, &version))
+                })
+                .collect::<Result<Vec<_>, OrcaError>>()?;
+        annotation_files.sort();
+        let annotations = annotation_files
+            .iter()
+            .map(|file| Ok(serde_yaml::from_str(&fs::read_to_string(file)?)?))
+            .collect::<Result<Vec<Annotation>, OrcaError>>()?;
+
+        let first_annotation_file = annotation_files.first().ok_or_else(|| NoAnnotationFound {
+            class: class.clone(),
+            name: hash.clone(),
+            version: "*".to_owned(),
+        })?;
+        let model = self.read_model::<T>(first_annotation_file, &hash)?;
+        Ok((model, annotations))
+    }
+
+    #[instrument(skip(self), fields(class = T::class()))]
+    fn verify<T: Model>(&self) -> Result<VerifyReport, OrcaError> {
+        let class = T::class();
+        let annotations =
+            Self::parse_annotation_path(&self.make_annotation_path(&class, "*", "*", "*"))?
+                .map(|metadata| {
+                    let (name, (hash, version)) = metadata?;
+                    let annotation_file = self.make_annotation_path(&class, &hash, &name, &version);
+                    Ok(StoredEntry {
+                        location: annotation_file.to_string_lossy().to_string(),
+                        hash,
+                        content: fs::read_to_string(&annotation_file).map_err(Into::into),
+                        format: SpecFormat::Yaml,
+                    })
+                })
+                .collect::<Result<Vec<_>, OrcaError>>()?;
+        let spec_dirs = self.directory.join(&class);
+        let specs = glob::glob(&format!(
+            "{}/*/",
+            Pattern::escape(&spec_dirs.to_string_lossy())
+        ))?
+        .map(|filepath| {
+            let spec_dir = filepath?;
+            let hash = decode_hash(&spec_dir.file_name().unwrap_or_default().to_string_lossy());
+            let (spec_file, format) = self
+                .stored_spec(&class, &hash)?
+                .unwrap_or_else(|| (self.make_spec_path(&class, &hash), self.spec_format));
+            Ok(StoredEntry {
+                location: spec_file.to_string_lossy().to_string(),
+                hash,
+                content: fs::read_to_string(&spec_file).map_err(Into::into),
+                format,
+            })
+        })
+        .collect::<Result<Vec<_>, OrcaError>>()?;
+
+        let mut report = verify_entries::<T>(annotations, specs);
+        // files not following the layout are never listed so surface them here as well
+        let annotation_dirs = self.directory.join("annotation").join(&class);
+        report.unparsable.extend(
+            self.fsck()?
+                .into_iter()
+                .filter(|found| found.path.starts_with(&annotation_dirs))
+                .map(|found| Unparsable {
+                    location: found.path.to_string_lossy().to_string(),
+                    reason: found.reason,
+                }),
+        );
+        Ok(report)
+    }
 }
 
 impl LocalFileStore {
+    /// Store writing specs as YAML, see `with_spec_format` for the others.
     pub fn new(directory: impl Into<PathBuf>) -> Self {
         Self {
             directory: directory.into(),
+            spec_format: SpecFormat::default(),
+            hash_algorithm: None,
+        }
+    }
+
+    /// Same store, writing new specs in `spec_format` instead.
+    #[must_use]
+    pub const fn with_spec_format(mut self, spec_format: SpecFormat) -> Self {
+        self.spec_format = spec_format;
+        self
+    }
+
+    /// Same store, rehashing models saved to it with `algorithm`.
+    #[must_use]
+    pub const fn with_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = Some(algorithm);
+        self
+    }
+
+    /// Model under the annotation in `annotation_file` along with its spec.
+    fn read_model<T: Model>(&self, annotation_file: &Path, hash: &str) -> Result<T, OrcaError> {
+        let (spec_file, format) =
+            self.stored_spec(&T::class(), hash)?
+                .ok_or_else(|| NoSpecFound {
+                    class: T::class(),
+                    hash: hash.to_owned(),
+                })?;
+        from_spec(
+            &fs::read_to_string(annotation_file)?,
+            &fs::read_to_string(spec_file)?,
+            hash,
+            format,
+        )
+    }
+
+    /// Mark every spec reachable from an annotation, following references between specs, then
+    /// sweep the rest. Refuses to run on a store holding models of classes other than the
+    /// built-in ones, since their references can't be followed.
+    #[instrument(skip(self))]
+    pub fn gc(&self, options: &GcOptions) -> Result<GcReport, OrcaError> {
+        let classes = BTreeMap::from([
+            (
+                Pod::class(),
+                Self::read_references::<Pod> as ReferenceReader,
+            ),
+            (PodJob::class(), Self::read_references::<PodJob>),
+            (PodResult::class(), Self::read_references::<PodResult>),
+            (Pipeline::class(), Self::read_references::<Pipeline>),
+        ]);
+        // references held by other classes can't be followed, so their targets could be swept
+        let unknown = self.unknown_classes(&classes)?;
+        if !unknown.is_empty() {
+            return Err(OrcaError::from(UnknownModelClass { classes: unknown }));
+        }
+
+        let mut queue = vec![];
+        for class in classes.keys() {
+            queue.extend(
+                Self::parse_annotation_path(&self.make_annotation_path(class, "*", "*", "*"))?
+                    .map(|metadata| Ok((class.clone(), metadata?.1 .0)))
+                    .collect::<Result<Vec<_>, OrcaError>>()?,
+            );
+        }
+        let mut marked = queue.iter().cloned().collect::<BTreeSet<_>>();
+        while let Some((class, hash)) = queue.pop() {
+            let references = classes
+                .get(&class)
+                .map_or_else(|| Ok(vec![]), |read| read(self, &hash))?;
+            queue.extend(
+                references
+                    .into_iter()
+                    .filter(|reference| marked.insert(reference.clone())),
+            );
         }
+
+        let mut report = GcReport::default();
+        for class in classes.keys() {
+            let unreachable = self
+                .spec_hashes(class)?
+                .into_iter()
+                .filter(|hash| !marked.contains(&(class.clone(), hash.clone())));
+            for hash in unreachable {
+                let spec_dir = self.directory.join(class).join(encode_hash(&hash));
+                report.unreachable.push(spec_dir.clone());
+                if fs::metadata(&spec_dir)?
+                    .modified()?
+                    .elapsed()
+                    .map_or(true, |age| age < options.grace_period)
+                {
+                    report.within_grace_period.push(spec_dir);
+                } else if !options.dry_run && self.sweep_spec(class, &hash)? {
+                    report.removed.push(spec_dir);
+                }
+            }
+        }
+        if !options.dry_run {
+            report.removed_locks = self.prune_locks()?;
+        }
+        info!(
+            unreachable = report.unreachable.len(),
+            removed = report.removed.len(),
+            removed_locks = report.removed_locks.len(),
+            dry_run = options.dry_run,
+            "Collected garbage."
+        );
+        Ok(report)
+    }
+
+    /// Only references by hash, ones by annotation are kept alive by the annotation itself.
+    fn read_references<T: Model>(&self, hash: &str) -> Result<Vec<(String, String)>, OrcaError> {
+        // a missing spec has nothing left to keep alive, `verify` reports it as dangling
+        let Some((spec_file, format)) = self.stored_spec(&T::class(), hash)? else {
+            return Ok(vec![]);
+        };
+        Ok(
+            parse_spec::<T>(&fs::read_to_string(&spec_file)?, hash, format)?
+                .references()
+                .into_iter()
+                .filter_map(|reference| match reference {
+                    Reference::Hash {
+                        class,
+                        hash: referenced_hash,
+                    } => Some((class, referenced_hash)),
+                    Reference::Annotation { .. } => None,
+                })
+                .collect(),
+        )
+    }
+
+    /// Spec stored under `hash` along with the format it was written in, which may not be
+    /// `spec_format` if the store was written with another one.
+    fn stored_spec(
+        &self,
+        class: &str,
+        hash: &str,
+    ) -> Result<Option<(PathBuf, SpecFormat)>, OrcaError> {
+        let spec_dir = self.directory.join(class).join(encode_hash(hash));
+        let spec_files = glob::glob(&format!(
+            "{}/spec.*",
+            Pattern::escape(&spec_dir.to_string_lossy())
+        ))?
+        .collect::<Result<Vec<_>, _>>()?;
+        Ok(spec_files.into_iter().find_map(|spec_file| {
+            let format = SpecFormat::from_extension(&spec_file.extension()?.to_string_lossy())?;
+            Some((spec_file, format))
+        }))
+    }
+
+    fn spec_hashes(&self, class: &str) -> Result<Vec<String>, OrcaError> {
+        glob::glob(&format!(
+            "{}/*/",
+            Pattern::escape(&self.directory.join(class).to_string_lossy())
+        ))?
+        .map(|filepath| {
+            Ok(decode_hash(
+                &filepath?.file_name().unwrap_or_default().to_string_lossy(),
+            ))
+        })
+        .collect()
+    }
+
+    /// Classes with models in the store other than the ones in `classes`.
+    fn unknown_classes(
+        &self,
+        classes: &BTreeMap<String, ReferenceReader>,
+    ) -> Result<Vec<String>, OrcaError> {
+        let root = Pattern::escape(&self.directory.to_string_lossy());
+        let found = glob::glob(&format!("{root}/*/"))?
+            .chain(glob::glob(&format!("{root}/annotation/*/"))?)
+            .map(|class_dir| {
+                Ok(class_dir?
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string())
+            })
+            .collect::<Result<BTreeSet<_>, OrcaError>>()?;
+        Ok(found
+            .into_iter()
+            .filter(|class| {
+                !classes.contains_key(class) && !STORE_DIRECTORIES.contains(&class.as_str())
+            })
+            .collect())
+    }
+
+    /// Remove an unreachable spec unless an annotation for it, or a spec referencing it, landed
+    /// since marking.
+    fn sweep_spec(&self, class: &str, hash: &str) -> Result<bool, OrcaError> {
+        let hash_lock = self.lock(LockScope::Hash, class, hash)?;
+        if Self::parse_annotation_path(&self.make_annotation_path(class, hash, "*", "*"))?
+            .next()
+            .is_some()
+            || !hash_referrers(self, class, hash)?.is_empty()
+        {
+            return Ok(false);
+        }
+        let spec_dir = self.directory.join(class).join(encode_hash(hash));
+        fs::remove_dir_all(&spec_dir)?;
+        info!(path = %spec_dir.display(), "Removed unreachable spec.");
+        hash_lock.remove()?;
+        Ok(true)
+    }
+
+    /// Hash locks of every spec `model` references by hash, taken after its own. References only
+    /// point from one class to another without cycles, so this can't deadlock.
+    fn lock_references<T: Model>(&self, model: &T) -> Result<Vec<StoreLock>, OrcaError> {
+        model
+            .references()
+            .into_iter()
+            .filter_map(|reference| match reference {
+                Reference::Hash { class, hash } => Some((class, hash)),
+                Reference::Annotation { .. } => None,
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|(class, hash)| self.lock(LockScope::Hash, &class, &hash))
+            .collect()
+    }
+
+    /// Block until this process holds the advisory lock for `key` in `scope`, released when the
+    /// returned guard is dropped. Locks are always taken name first, then hash, then the hashes
+    /// it references, to avoid deadlocks.
+    fn lock(&self, scope: LockScope, class: &str, key: &str) -> Result<StoreLock, OrcaError> {
+        let lock_file = self.make_lock_path(scope, class, key);
+        fs::create_dir_all(lock_file.parent().ok_or_else(|| FileHasNoParent {
+            path: lock_file.clone(),
+        })?)?;
+        let file = Self::lock_file(&lock_file)?;
+        debug!(path = %lock_file.display(), "Acquired lock.");
+        Ok(StoreLock {
+            file,
+            path: lock_file,
+        })
+    }
+
+    /// Lock the file at `lock_file`, starting over with a fresh one if its previous holder removed
+    /// it while this one waited on it.
+    fn lock_file(lock_file: &Path) -> Result<File, OrcaError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(lock_file)?;
+        file.lock_exclusive()?;
+        if Self::still_linked(&file, lock_file) {
+            Ok(file)
+        } else {
+            Self::lock_file(lock_file)
+        }
+    }
+
+    fn make_lock_path(&self, scope: LockScope, class: &str, key: &str) -> PathBuf {
+        let (scope_name, file_key) = match scope {
+            LockScope::Name => ("name", key.to_owned()),
+            LockScope::Hash => ("hash", encode_hash(key)),
+        };
+        self.directory
+            .join(format!(".lock/{scope_name}/{class}/{file_key}.lock"))
+    }
+
+    /// Whether `file` is still the one at `path`.
+    #[cfg(unix)]
+    fn still_linked(file: &File, path: &Path) -> bool {
+        match (file.metadata(), fs::metadata(path)) {
+            (Ok(locked), Ok(current)) => {
+                locked.dev() == current.dev() && locked.ino() == current.ino()
+            }
+            (Err(_) | Ok(_), _) => false,
+        }
+    }
+
+    /// Whether `file` is still the one at `path`. Open files can't be removed here, so it is as
+    /// long as the path exists.
+    #[cfg(not(unix))]
+    fn still_linked(_file: &File, path: &Path) -> bool {
+        path.exists()
+    }
+
+    /// Remove lock files whose annotation name or spec no longer exists, each under its own lock.
+    fn prune_locks(&self) -> Result<Vec<PathBuf>, OrcaError> {
+        let lock_root = Pattern::escape(&self.directory.join(".lock").to_string_lossy());
+        glob::glob(&format!("{lock_root}/*/*/*.lock"))?
+            .map(|filepath| self.prune_lock(&filepath?))
+            .filter_map(Result::transpose)
+            .collect()
+    }
+
+    fn prune_lock(&self, lock_file: &Path) -> Result<Option<PathBuf>, OrcaError> {
+        let mut names = lock_file.ancestors().take(3).map(|path| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        });
+        let (Some(file_name), Some(class), Some(scope_name)) =
+            (names.next(), names.next(), names.next())
+        else {
+            return Ok(None);
+        };
+        let key = file_name.trim_end_matches(".lock");
+        let (scope, guarded) = match scope_name.as_str() {
+            "name" => (
+                LockScope::Name,
+                self.directory.join("annotation").join(&class).join(key),
+            ),
+            "hash" => (LockScope::Hash, self.directory.join(&class).join(key)),
+            _ => return Ok(None),
+        };
+
+        let lock = self.lock(scope, &class, key)?;
+        if guarded.exists() {
+            return Ok(None);
+        }
+        lock.remove()?;
+        Ok(Some(lock_file.to_path_buf()))
+    }
+
+    /// Store in `directory`, after running [`LocalFileStore::recover`] to clean up after writes
+    /// interrupted by a crash.
+    pub fn open(directory: impl Into<PathBuf>) -> Result<Self, OrcaError> {
+        let store = Self::new(directory);
+        let recovery = store.recover()?;
+        if !recovery.removed_temp_files.is_empty() || !recovery.quarantined.is_empty() {
+            warn!(
+                removed_temp_files = recovery.removed_temp_files.len(),
+                quarantined = recovery.quarantined.len(),
+                "Recovered store from interrupted writes."
+            );
+        }
+        Ok(store)
+    }
+
+    /// Remove temporary files left by interrupted writes and move annotations whose spec is
+    /// missing under `quarantine/` so they no longer break loading. Temporary files younger than
+    /// ten minutes are kept, since another process sharing the directory may still be writing them.
+    #[instrument(skip(self))]
+    pub fn recover(&self) -> Result<Recovery, OrcaError> {
+        let root = Pattern::escape(&self.directory.to_string_lossy());
+        let removed_temp_files = glob::glob(&format!("{root}/**/.*.tmp"))?
+            .map(|filepath| Self::remove_stale_temp_file(filepath?))
+            .filter_map(Result::transpose)
+            .collect::<Result<Vec<_>, OrcaError>>()?;
+
+        let file_re = Regex::new(ANNOTATION_FILE_PATTERN)?;
+        let quarantined = glob::glob(&format!("{root}/annotation/*/*/*.yaml"))?
+            .map(|filepath| self.quarantine_dangling(&filepath?, &file_re))
+            .filter_map(Result::transpose)
+            .collect::<Result<Vec<_>, OrcaError>>()?;
+
+        Ok(Recovery {
+            removed_temp_files,
+            quarantined,
+        })
+    }
+
+    /// Remove `temp_file` unless it is recent enough for its writer to still be at it.
+    fn remove_stale_temp_file(temp_file: PathBuf) -> Result<Option<PathBuf>, OrcaError> {
+        let age = fs::metadata(&temp_file)?
+            .modified()?
+            .elapsed()
+            .unwrap_or_default();
+        if age < Duration::from_secs(STALE_TEMP_FILE_SECONDS) {
+            return Ok(None);
+        }
+        fs::remove_file(&temp_file)?;
+        debug!(path = %temp_file.display(), "Removed temporary file.");
+        Ok(Some(temp_file))
+    }
+
+    fn quarantine_dangling(
+        &self,
+        annotation_file: &Path,
+        file_re: &Regex,
+    ) -> Result<Option<PathBuf>, OrcaError> {
+        let component = |depth| {
+            annotation_file
+                .ancestors()
+                .nth(depth)
+                .and_then(Path::file_name)
+                .map(|found| found.to_string_lossy().to_string())
+        };
+        let (Some(class), Some(name), Some(group)) = (
+            component(2),
+            component(1),
+            annotation_file
+                .file_name()
+                .and_then(|file| file_re.captures(file.to_str()?)),
+        ) else {
+            return Ok(None);
+        };
+        let hash = decode_hash(&group["hash"]);
+        if self.stored_spec(&class, &hash)?.is_some() {
+            return Ok(None);
+        }
+        // check again once a concurrent save or delete of the annotation is done with it
+        let _name_lock = self.lock(LockScope::Name, &class, &name)?;
+        let _hash_lock = self.lock(LockScope::Hash, &class, &hash)?;
+        if !annotation_file.exists() || self.stored_spec(&class, &hash)?.is_some() {
+            return Ok(None);
+        }
+
+        let destination = self
+            .directory
+            .join("quarantine")
+            .join(annotation_file.strip_prefix(&self.directory)?);
+        let destination_directory = destination.parent().ok_or_else(|| FileHasNoParent {
+            path: destination.clone(),
+        })?;
+        let synced_directory = Self::create_directory(destination_directory)?;
+        fs::rename(annotation_file, &destination)?;
+        Self::sync_directories(destination_directory, &synced_directory)?;
+        if let Some(source_directory) = annotation_file.parent() {
+            Self::sync_directories(source_directory, source_directory)?;
+        }
+        warn!(
+            path = %annotation_file.display(),
+            quarantine = %destination.display(),
+            "Quarantined annotation whose spec is missing."
+        );
+        Ok(Some(destination))
     }
 
     pub fn make_annotation_path(
@@ -133,56 +959,124 @@ impl LocalFileStore {
             "annotation",
             class,
             name,
-            hash,
+            encode_hash(hash),
             version,
         ))
     }
 
+    /// Where a new spec is written, see [`LocalFileStore::spec_format`].
     pub fn make_spec_path(&self, class: &str, hash: &str) -> PathBuf {
         PathBuf::from(format!(
-            "{}/{}/{}/{}",
+            "{}/{}/{}/spec.{}",
             self.directory.to_string_lossy(),
             class,
-            hash,
-            "spec.yaml",
+            encode_hash(hash),
+            self.spec_format.extension(),
+        ))
+    }
+
+    pub fn make_tag_path(&self, class: &str, name: &str, tag: &str) -> PathBuf {
+        PathBuf::from(format!(
+            "{}/{}/{}/{}/{}.yaml",
+            self.directory.to_string_lossy(),
+            "tag",
+            class,
+            name,
+            tag,
         ))
     }
 
+    /// Report annotation files that don't follow the store layout and are skipped when listing.
+    #[instrument(skip(self))]
+    pub fn fsck(&self) -> Result<Vec<NonConformingFile>, OrcaError> {
+        let root = self.directory.join("annotation");
+        let file_re = Regex::new(ANNOTATION_FILE_PATTERN)?;
+        glob::glob(&format!(
+            "{}/**/*",
+            Pattern::escape(&root.to_string_lossy())
+        ))?
+        .map(|filepath| Self::check_annotation_file(&root, filepath?, &file_re))
+        .filter_map(Result::transpose)
+        .collect()
+    }
+
+    fn check_annotation_file(
+        root: &Path,
+        path: PathBuf,
+        file_re: &Regex,
+    ) -> Result<Option<NonConformingFile>, OrcaError> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let components = path
+            .strip_prefix(root)?
+            .iter()
+            .map(|component| component.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        let reason = if let [_, name, file] = components.as_slice() {
+            match Annotation::validate_name(name) {
+                Err(error) => Some(format!("name {}", error.rule)),
+                Ok(()) => (!file_re.is_match(file))
+                    .then(|| "file name is not `<hash>-<version>.yaml`".to_owned()),
+            }
+        } else {
+            Some("not at `annotation/<class>/<name>/<file>`".to_owned())
+        };
+        Ok(reason.map(|found_reason| NonConformingFile {
+            path,
+            reason: found_reason,
+        }))
+    }
+
+    /// Non-conforming files are skipped, see [`LocalFileStore::fsck`].
     fn parse_annotation_path(
         path: &Path,
-    ) -> Result<
-        Map<
-            Paths,
-            impl FnMut(Result<PathBuf, GlobError>) -> Result<(String, (String, String)), Box<dyn Error>>,
-        >,
-        Box<dyn Error>,
-    > {
-        let paths = glob::glob(&path.to_string_lossy())?.map(|filepath| {
-            let re = Regex::new(
-                r"(?x)
-                ^.*
-                \/(?<name>[0-9a-zA-Z\-]+)
-                \/
-                    (?<hash>[0-9A-F]+)
-                    -
-                    (?<version>[0-9]+\.[0-9]+\.[0-9]+)
-                    \.yaml
-                $",
-            )?;
-            let filepath_string = String::from(filepath?.to_string_lossy());
-            let group = re.captures(&filepath_string).ok_or(NoRegexMatch {})?;
-            Ok((
-                group["name"].to_string(),
-                (group["hash"].to_string(), group["version"].to_string()),
-            ))
+    ) -> Result<impl Iterator<Item = AnnotationMetadata>, OrcaError> {
+        let file_re = Regex::new(ANNOTATION_FILE_PATTERN)?;
+        debug!(pattern = %path.display(), "Scanning annotations.");
+        let paths = glob::glob(&path.to_string_lossy())?.filter_map(move |filepath| {
+            let found = match filepath {
+                Ok(found) => found,
+                Err(error) => return Some(Err(error.into())),
+            };
+            let name = found.parent()?.file_name()?.to_string_lossy().to_string();
+            let file = found.file_name()?.to_string_lossy().to_string();
+            let group = file_re.captures(&file)?;
+            Annotation::validate_name(&name).ok()?;
+            Some(Ok((
+                name,
+                (decode_hash(&group["hash"]), group["version"].to_string()),
+            )))
         });
 
         Ok(paths)
     }
 
-    fn get_pod_version_map(&self, name: &str) -> Result<BTreeMap<String, String>, Box<dyn Error>> {
-        Self::parse_annotation_path(&self.make_annotation_path("pod", "*", name, "*"))?
-            .map(|metadata| -> Result<(String, String), Box<dyn Error>> {
+    /// Throw an error if the annotation is already taken, even by a different spec.
+    fn check_annotation_free(
+        &self,
+        class: &str,
+        name: &str,
+        version: &str,
+    ) -> Result<(), OrcaError> {
+        self.get_version_map(class, name)?
+            .get(version)
+            .map_or(Ok(()), |_| {
+                Err(OrcaError::from(AnnotationExists {
+                    class: class.to_owned(),
+                    name: name.to_owned(),
+                    version: version.to_owned(),
+                }))
+            })
+    }
+
+    fn get_version_map(
+        &self,
+        class: &str,
+        name: &str,
+    ) -> Result<BTreeMap<String, String>, OrcaError> {
+        Self::parse_annotation_path(&self.make_annotation_path(class, "*", name, "*"))?
+            .map(|metadata| -> Result<(String, String), OrcaError> {
                 let resolved_metadata = metadata?;
                 let hash = resolved_metadata.1 .0;
                 let version = resolved_metadata.1 .1;
@@ -191,23 +1085,99 @@ impl LocalFileStore {
             .collect::<Result<BTreeMap<String, String>, _>>()
     }
 
-    fn save_file(file: &Path, content: &str, fail_if_exists: bool) -> Result<(), Box<dyn Error>> {
-        fs::create_dir_all(file.parent().ok_or_else(|| FileHasNoParent {
+    /// Writes go to a temporary file in the same directory that is then moved into place, so
+    /// readers only ever see complete files.
+    fn save_file(file: &Path, content: &str, mode: SaveMode) -> Result<(), OrcaError> {
+        let directory = file.parent().ok_or_else(|| FileHasNoParent {
             path: file.to_path_buf(),
-        })?)?;
+        })?;
+        let synced_directory = Self::create_directory(directory)?;
         let file_exists = file.exists();
-        if file_exists && fail_if_exists {
-            return Err(Box::new(FileExists {
+        if file_exists && mode == SaveMode::CreateNew {
+            return Err(OrcaError::from(FileExists {
                 path: file.to_path_buf(),
             }));
-        } else if file_exists {
-            println!(
-                "Skip saving `{}` since it is already stored.",
-                file.to_string_lossy().bright_cyan(),
-            );
+        } else if file_exists && mode == SaveMode::KeepExisting {
+            debug!(path = %file.display(), "Skip saving since it is already stored.");
+            return Ok(());
+        }
+
+        let temp_file = directory.join(format!(
+            ".{}.{}-{}.tmp",
+            file.file_name().unwrap_or_default().to_string_lossy(),
+            process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed),
+        ));
+        let mut temp = File::create_new(&temp_file)?;
+        temp.write_all(content.as_bytes())?;
+        temp.sync_all()?;
+
+        // linking refuses to replace a file published by a concurrent writer, renaming replaces
+        // it with identical content or on purpose
+        let published = if mode == SaveMode::CreateNew {
+            fs::hard_link(&temp_file, file).and_then(|()| fs::remove_file(&temp_file))
         } else {
-            fs::write(file, content)?;
+            fs::rename(&temp_file, file)
+        };
+        if let Err(error) = published {
+            // report why publishing failed, not a temp file that is already gone
+            Self::discard_temp_file(&temp_file);
+            return Err(Self::publish_error(file, error));
         }
+        Self::sync_directories(directory, &synced_directory)?;
+        debug!(path = %file.display(), bytes = content.len(), "Saved file.");
+        Ok(())
+    }
+
+    /// Create `directory` along with any missing parents, returning the closest ancestor that
+    /// already existed, up to which [`LocalFileStore::sync_directories`] has to sync.
+    fn create_directory(directory: &Path) -> Result<PathBuf, OrcaError> {
+        let existing = directory
+            .ancestors()
+            .find(|ancestor| ancestor.is_dir())
+            .unwrap_or(directory)
+            .to_path_buf();
+        fs::create_dir_all(directory)?;
+        Ok(existing)
+    }
+
+    /// Flush the entries of `directory` and each of its ancestors up to `existing`, since a
+    /// renamed or linked file, or a new directory, is only durable once its parent is.
+    #[cfg(unix)]
+    fn sync_directories(directory: &Path, existing: &Path) -> Result<(), OrcaError> {
+        let created = directory
+            .ancestors()
+            .take_while(|ancestor| *ancestor != existing)
+            .count();
+        directory
+            .ancestors()
+            .take(created + 1)
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .try_for_each(|ancestor| File::open(ancestor)?.sync_all())?;
         Ok(())
     }
+
+    /// Directories can't be opened for syncing on this platform.
+    #[cfg(not(unix))]
+    fn sync_directories(_directory: &Path, _existing: &Path) -> Result<(), OrcaError> {
+        Ok(())
+    }
+
+    /// Best effort, since failing to clean up shouldn't hide why the save failed.
+    fn discard_temp_file(temp_file: &Path) {
+        if let Err(cleanup) = fs::remove_file(temp_file) {
+            warn!(path = %temp_file.display(), %cleanup, "Failed to remove temporary file.");
+        }
+    }
+
+    fn publish_error(file: &Path, error: io::Error) -> OrcaError {
+        if error.kind() == ErrorKind::AlreadyExists {
+            FileExists {
+                path: file.to_path_buf(),
+            }
+            .into()
+        } else {
+            error.into()
+        }
+    }
 }
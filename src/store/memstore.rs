@@ -0,0 +1,331 @@
+use crate::{
+    error::{AnnotationExists, LockPoisoned, NoAnnotationFound, NoSpecFound, OrcaError},
+    model::{from_yaml_str, to_yaml, Annotation, HashAlgorithm, Model, SpecFormat},
+    store::{
+        resolve_hash_prefix, verify_entries, ListFilter, Record, Store, StoredEntry, TagMove,
+        VerifyReport,
+    },
+};
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    sync::{Mutex, MutexGuard},
+};
+
+/// `(class, name, version)` of a stored annotation.
+type AnnotationKey = (String, String, String);
+/// `(class, name, tag)` of a tag.
+type TagKey = (String, String, String);
+
+#[derive(Debug, Default)]
+struct State {
+    annotations: BTreeMap<AnnotationKey, (String, String)>, // key -> (hash, annotation yaml)
+    specs: BTreeMap<(String, String), String>,              // (class, hash) -> spec yaml
+    tags: BTreeMap<TagKey, Vec<TagMove>>,
+}
+
+/// Store that keeps the same YAML a `LocalFileStore` would write, but in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryStore {
+    state: Mutex<State>,
+    hash_algorithm: Option<HashAlgorithm>,
+}
+
+impl Store for InMemoryStore {
+    fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        self.hash_algorithm
+    }
+
+    fn save_hashed<T: Model>(&self, model: &T) -> Result<(), OrcaError> {
+        let class = T::class();
+        let annotation = model.annotation();
+        annotation.validate()?;
+        let key = (
+            class.clone(),
+            annotation.name.clone(),
+            annotation.version.to_string(),
+        );
+        let mut state = self.state()?;
+
+        if state.annotations.contains_key(&key) {
+            return Err(OrcaError::from(AnnotationExists {
+                class,
+                name: annotation.name.clone(),
+                version: annotation.version.to_string(),
+            }));
+        }
+        // many annotations may share a single spec so only the first save writes it
+        if let Entry::Vacant(spec) = state.specs.entry((class, model.hash().to_owned())) {
+            spec.insert(to_yaml::<T>(model)?);
+        }
+        state.annotations.insert(
+            key,
+            (model.hash().to_owned(), serde_yaml::to_string(annotation)?),
+        );
+        drop(state);
+
+        Ok(())
+    }
+
+    fn load<T: Model>(&self, name: &str, version: &str) -> Result<T, OrcaError> {
+        let class = T::class();
+        let resolved = self.resolve_version::<T>(name, version)?.to_string();
+        let state = self.state()?;
+
+        let (hash, annotation_yaml) = state
+            .annotations
+            .get(&(class.clone(), name.to_owned(), resolved))
+            .ok_or_else(|| NoAnnotationFound {
+                class: class.clone(),
+                name: name.to_owned(),
+                version: version.to_owned(),
+            })?;
+
+        from_yaml_str::<T>(annotation_yaml, &state.specs[&(class, hash.clone())], hash)
+    }
+
+    fn list_iter<T: Model>(
+        &self,
+        filter: &ListFilter,
+    ) -> Result<impl Iterator<Item = Result<Record, OrcaError>> + '_, OrcaError> {
+        let class = T::class();
+        #[expect(
+            clippy::needless_collect,
+            reason = "Snapshot so the lock isn't held while the caller iterates."
+        )]
+        let records = self
+            .state()?
+            .annotations
+            .iter()
+            .filter(|((list_class, _, _), _)| list_class == &class)
+            .map(|((_, name, version), (hash, annotation_yaml))| {
+                let annotation: Annotation = serde_yaml::from_str(annotation_yaml)?;
+                Ok(Record {
+                    name: name.clone(),
+                    version: version.parse()?,
+                    hash: hash.clone(),
+                    description: annotation.description,
+                })
+            })
+            .filter(|record| record.as_ref().map_or(true, |found| filter.matches(found)))
+            .collect::<Vec<_>>();
+
+        Ok(records.into_iter())
+    }
+
+    fn delete_unchecked<T: Model>(&self, name: &str, version: &str) -> Result<(), OrcaError> {
+        let class = T::class();
+        let mut state = self.state()?;
+
+        let (hash, _) = state
+            .annotations
+            .remove(&(class.clone(), name.to_owned(), version.to_owned()))
+            .ok_or_else(|| NoAnnotationFound {
+                class: class.clone(),
+                name: name.to_owned(),
+                version: version.to_owned(),
+            })?;
+        if !state
+            .annotations
+            .iter()
+            .any(|((list_class, _, _), (list_hash, _))| list_class == &class && list_hash == &hash)
+        {
+            state.specs.remove(&(class, hash));
+        }
+        drop(state);
+
+        Ok(())
+    }
+
+    fn save_annotation<T: Model>(
+        &self,
+        hash: &str,
+        annotation: &Annotation,
+    ) -> Result<(), OrcaError> {
+        let class = T::class();
+        annotation.validate()?;
+        let key = (
+            class.clone(),
+            annotation.name.clone(),
+            annotation.version.to_string(),
+        );
+        let mut state = self.state()?;
+
+        if state.annotations.contains_key(&key) {
+            return Err(OrcaError::from(AnnotationExists {
+                class,
+                name: annotation.name.clone(),
+                version: annotation.version.to_string(),
+            }));
+        }
+        if !state.specs.contains_key(&(class.clone(), hash.to_owned())) {
+            return Err(OrcaError::from(NoSpecFound {
+                class,
+                hash: hash.to_owned(),
+            }));
+        }
+        state
+            .annotations
+            .insert(key, (hash.to_owned(), serde_yaml::to_string(annotation)?));
+        drop(state);
+
+        Ok(())
+    }
+
+    fn replace_annotation<T: Model>(&self, annotation: &Annotation) -> Result<(), OrcaError> {
+        let class = T::class();
+        let mut state = self.state()?;
+
+        let (_, annotation_yaml) = state
+            .annotations
+            .get_mut(&(
+                class.clone(),
+                annotation.name.clone(),
+                annotation.version.to_string(),
+            ))
+            .ok_or_else(|| NoAnnotationFound {
+                class,
+                name: annotation.name.clone(),
+                version: annotation.version.to_string(),
+            })?;
+        *annotation_yaml = serde_yaml::to_string(annotation)?;
+        drop(state);
+
+        Ok(())
+    }
+
+    fn replace_spec<T: Model>(&self, model: &T) -> Result<(), OrcaError> {
+        let class = T::class();
+        let mut state = self.state()?;
+
+        let spec_yaml = state
+            .specs
+            .get_mut(&(class.clone(), model.hash().to_owned()))
+            .ok_or_else(|| NoSpecFound {
+                class,
+                hash: model.hash().to_owned(),
+            })?;
+        *spec_yaml = to_yaml::<T>(model)?;
+        drop(state);
+
+        Ok(())
+    }
+
+    fn append_tag_move<T: Model>(
+        &self,
+        name: &str,
+        tag: &str,
+        tag_move: &TagMove,
+    ) -> Result<(), OrcaError> {
+        self.state()?
+            .tags
+            .entry((T::class(), name.to_owned(), tag.to_owned()))
+            .or_default()
+            .push(tag_move.clone());
+        Ok(())
+    }
+
+    fn tag_log<T: Model>(&self, name: &str) -> Result<BTreeMap<String, Vec<TagMove>>, OrcaError> {
+        let class = T::class();
+        Ok(self
+            .state()?
+            .tags
+            .iter()
+            .filter(|((list_class, list_name, _), _)| list_class == &class && list_name == name)
+            .map(|((_, _, tag), moves)| (tag.clone(), moves.clone()))
+            .collect())
+    }
+
+    fn load_by_hash<T: Model>(&self, hash_prefix: &str) -> Result<(T, Vec<Annotation>), OrcaError> {
+        let class = T::class();
+        let state = self.state()?;
+
+        let hash = resolve_hash_prefix(
+            &class,
+            hash_prefix,
+            state
+                .specs
+                .keys()
+                .filter(|(list_class, _)| list_class == &class)
+                .map(|(_, hash)| hash.clone()),
+        )?;
+        // annotations are keyed by (class, name, version) so they come out sorted
+        let annotation_yamls = state
+            .annotations
+            .iter()
+            .filter(|((list_class, _, _), (list_hash, _))| {
+                list_class == &class && list_hash == &hash
+            })
+            .map(|(_, (_, annotation_yaml))| annotation_yaml)
+            .collect::<Vec<_>>();
+        let annotations = annotation_yamls
+            .iter()
+            .map(|annotation_yaml| serde_yaml::from_str(annotation_yaml))
+            .collect::<Result<Vec<Annotation>, _>>()?;
+
+        let first_annotation_yaml = annotation_yamls.first().ok_or_else(|| NoAnnotationFound {
+            class: class.clone(),
+            name: hash.clone(),
+            version: "*".to_owned(),
+        })?;
+        let model = from_yaml_str::<T>(
+            first_annotation_yaml,
+            &state.specs[&(class, hash.clone())],
+            &hash,
+        )?;
+        drop(state);
+        Ok((model, annotations))
+    }
+
+    fn verify<T: Model>(&self) -> Result<VerifyReport, OrcaError> {
+        let class = T::class();
+        let state = self.state()?;
+        let annotations = state
+            .annotations
+            .iter()
+            .filter(|((list_class, _, _), _)| list_class == &class)
+            .map(
+                |((_, name, version), (hash, annotation_yaml))| StoredEntry {
+                    location: format!("{name}:{version}"),
+                    hash: hash.clone(),
+                    content: Ok(annotation_yaml.clone()),
+                    format: SpecFormat::Yaml,
+                },
+            )
+            .collect();
+        let specs = state
+            .specs
+            .iter()
+            .filter(|((list_class, _), _)| list_class == &class)
+            .map(|((_, hash), spec_yaml)| StoredEntry {
+                location: hash.clone(),
+                hash: hash.clone(),
+                content: Ok(spec_yaml.clone()),
+                format: SpecFormat::Yaml,
+            })
+            .collect();
+        drop(state);
+        Ok(verify_entries::<T>(annotations, specs))
+    }
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Same store, rehashing models saved to it with `algorithm`.
+    #[must_use]
+    pub const fn with_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = Some(algorithm);
+        self
+    }
+
+    fn state(&self) -> Result<MutexGuard<'_, State>, OrcaError> {
+        self.state.lock().map_err(|_poisoned| {
+            LockPoisoned {
+                resource: "in-memory store".to_owned(),
+            }
+            .into()
+        })
+    }
+}
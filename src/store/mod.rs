@@ -1,11 +1,746 @@
-use crate::model::Pod;
-use std::{collections::BTreeMap, error::Error};
+use crate::{
+    error::{
+        AmbiguousHash, InvalidTag, NoAnnotationFound, NoSpecFound, OrcaError, StillReferenced,
+    },
+    model::{
+        canonical_spec_json, from_spec, to_canonical_json, Annotation, HashAlgorithm, Model,
+        Multihash, Pipeline, Pod, PodJob, PodRef, PodResult, Reference, Resolver, SpecFormat,
+        Version, VersionReq,
+    },
+};
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 pub trait Store {
-    fn save_pod(&self, pod: &Pod) -> Result<(), Box<dyn Error>>;
-    fn load_pod(&self, name: &str, version: &str) -> Result<Pod, Box<dyn Error>>;
-    fn list_pod(&self) -> Result<BTreeMap<String, Vec<String>>, Box<dyn Error>>;
-    fn delete_pod(&self, name: &str, version: &str) -> Result<(), Box<dyn Error>>;
+    /// Save `model`, rehashed first if the store has a `hash_algorithm` the model isn't hashed
+    /// with.
+    fn save<T: Model>(&self, model: &T) -> Result<(), OrcaError> {
+        match self.hash_algorithm() {
+            Some(algorithm) if model.hash().parse::<Multihash>()?.algorithm != algorithm => {
+                self.save_hashed(&model.with_hash_algorithm(algorithm)?)
+            }
+            Some(_) | None => self.save_hashed(model),
+        }
+    }
+    /// Save `model` under the hash it carries, whatever the store's `hash_algorithm`.
+    fn save_hashed<T: Model>(&self, model: &T) -> Result<(), OrcaError>;
+    /// Algorithm models are rehashed with on `save`, `None` keeps the one they were hashed with.
+    fn hash_algorithm(&self) -> Option<HashAlgorithm>;
+    /// `version` may be exact (`0.67.0`), a requirement (`^0.67`, `~1.2`), a tag (`stable`) or
+    /// `latest`. Requirements and `latest` resolve to the highest matching stored version, unless
+    /// `latest` was set as a tag.
+    fn load<T: Model>(&self, name: &str, version: &str) -> Result<T, OrcaError>;
+    /// Look up the pod behind a pipeline node, for use as the `resolve` of `Pipeline::new`.
+    fn resolve_pod_ref(&self, pod_ref: &PodRef) -> Result<Pod, OrcaError> {
+        match pod_ref {
+            PodRef::Hash(hash) => Ok(self.load_by_hash::<Pod>(hash)?.0),
+            PodRef::Annotation { name, version } => self.load::<Pod>(name, version),
+        }
+    }
+    fn list<T: Model>(&self, filter: &ListFilter) -> Result<Vec<Record>, OrcaError> {
+        self.list_iter::<T>(filter)?.collect()
+    }
+    /// Streaming variant of `list` for stores too large to collect at once.
+    fn list_iter<T: Model>(
+        &self,
+        filter: &ListFilter,
+    ) -> Result<impl Iterator<Item = Result<Record, OrcaError>> + '_, OrcaError>;
+    /// Delete an annotation, along with its spec once no other annotation points at it. Models
+    /// that would be left with a dangling reference are handled according to `mode`.
+    fn delete<T: Model>(
+        &self,
+        name: &str,
+        version: &str,
+        mode: DeleteMode,
+    ) -> Result<(), OrcaError> {
+        let referrers = self.referrers::<T>(name, version)?;
+        if referrers.is_empty() {
+            return self.delete_unchecked::<T>(name, version);
+        }
+        match mode {
+            DeleteMode::Restrict => Err(StillReferenced {
+                class: T::class(),
+                name: name.to_owned(),
+                version: version.to_owned(),
+                referrers: referrers
+                    .iter()
+                    .map(|(class, record)| format!("{class} {}:{}", record.name, record.version))
+                    .collect(),
+            }
+            .into()),
+            DeleteMode::Cascade => {
+                referrers
+                    .iter()
+                    .try_for_each(|(class, record)| delete_referrer(self, class, record))?;
+                self.delete_unchecked::<T>(name, version)
+            }
+        }
+    }
+    /// Delete an annotation without checking whether other models reference it.
+    fn delete_unchecked<T: Model>(&self, name: &str, version: &str) -> Result<(), OrcaError>;
+    /// Attach another annotation to an already stored spec, e.g. to give it a second name.
+    fn save_annotation<T: Model>(
+        &self,
+        hash: &str,
+        annotation: &Annotation,
+    ) -> Result<(), OrcaError>;
+    /// Overwrite the stored annotation with the same name and version, keeping its spec.
+    fn replace_annotation<T: Model>(&self, annotation: &Annotation) -> Result<(), OrcaError>;
+    /// Overwrite the spec stored under `model`'s hash, leaving its annotations as they are. Meant
+    /// for rewriting a spec in the current schema, the content has to keep hashing the same.
+    fn replace_spec<T: Model>(&self, model: &T) -> Result<(), OrcaError>;
+    /// Add `alias` as another annotation of the spec `name:version` points at. `version` is
+    /// anything accepted by `load`.
+    fn alias<T: Model>(
+        &self,
+        name: &str,
+        version: &str,
+        alias: &Annotation,
+    ) -> Result<(), OrcaError> {
+        let model = self.load::<T>(name, version)?;
+        self.save_annotation::<T>(model.hash(), alias)
+    }
+    /// Move `name:version` to another annotation of the same spec. Refused the same way as a
+    /// `DeleteMode::Restrict` delete if other models name the current annotation.
+    fn rename<T: Model>(
+        &self,
+        name: &str,
+        version: &str,
+        renamed: &Annotation,
+    ) -> Result<(), OrcaError> {
+        let model = self.load::<T>(name, version)?;
+        self.save_annotation::<T>(model.hash(), renamed)?;
+        self.delete::<T>(
+            name,
+            &model.annotation().version.to_string(),
+            DeleteMode::Restrict,
+        )
+        .or_else(|error| {
+            // keep the store as it was before the rename
+            self.delete_unchecked::<T>(&renamed.name, &renamed.version.to_string())?;
+            Err(error)
+        })
+    }
+    /// Change the description of `name:version`, leaving its spec and hash as they are.
+    fn update_description<T: Model>(
+        &self,
+        name: &str,
+        version: &str,
+        description: &str,
+    ) -> Result<(), OrcaError> {
+        let model = self.load::<T>(name, version)?;
+        self.replace_annotation::<T>(&Annotation {
+            name: name.to_owned(),
+            version: model.annotation().version.clone(),
+            description: description.to_owned(),
+        })
+    }
+    /// `(class, annotation)` of every stored model that deleting `name:version` would leave with
+    /// a dangling reference.
+    fn referrers<T: Model>(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Vec<(String, Record)>, OrcaError> {
+        let class = T::class();
+        let siblings = self.list::<T>(&ListFilter {
+            name: Some(Pattern::new(&Pattern::escape(name))?),
+            ..ListFilter::default()
+        })?;
+        let Some(target) = siblings
+            .iter()
+            .find(|record| record.version.to_string() == version)
+        else {
+            return Ok(vec![]);
+        };
+        // the spec only goes away with its last annotation
+        let spec_removed = !siblings
+            .iter()
+            .any(|record| record.hash == target.hash && record.version != target.version);
+        // a reference by annotation only dangles once no other version satisfies it
+        let other_versions = siblings
+            .iter()
+            .map(|record| &record.version)
+            .filter(|other| **other != target.version)
+            .collect::<Vec<_>>();
+        // a tag points at exactly one version, which takes precedence over requirements
+        let tags = self.tags::<T>(name)?;
+        let resolves_to = |requirement: &str, candidate: &Version| {
+            tags.get(requirement).map_or_else(
+                || version_matches(requirement, candidate),
+                |tagged| tagged == candidate,
+            )
+        };
+        let still_resolves = |requirement: &str| {
+            other_versions
+                .iter()
+                .any(|other| resolves_to(requirement, other))
+        };
+        let is_dangling = |reference: &Reference| match reference {
+            Reference::Hash {
+                class: referenced_class,
+                hash,
+            } => spec_removed && *referenced_class == class && *hash == target.hash,
+            Reference::Annotation {
+                class: referenced_class,
+                name: referenced_name,
+                version: requirement,
+            } => {
+                *referenced_class == class
+                    && referenced_name == name
+                    && resolves_to(requirement, &target.version)
+                    && !still_resolves(requirement)
+            }
+        };
+
+        referrers_matching(self, &is_dangling)
+    }
+    /// Resolve a version requirement as accepted by `load` to a stored version. `latest` skips
+    /// pre-releases, same as cargo's `*`.
+    fn resolve_version<T: Model>(
+        &self,
+        name: &str,
+        requirement: &str,
+    ) -> Result<Version, OrcaError> {
+        if let Ok(version) = Version::parse(requirement) {
+            return Ok(version);
+        }
+        if let Some(tagged) = self.tags::<T>(name)?.remove(requirement) {
+            return Ok(tagged);
+        }
+        let version_req = if requirement == "latest" {
+            VersionReq::STAR
+        } else {
+            VersionReq::parse(requirement)?
+        };
+        self.list::<T>(&ListFilter {
+            name: Some(Pattern::new(&Pattern::escape(name))?),
+            version: Some(version_req),
+            ..ListFilter::default()
+        })?
+        .into_iter()
+        .map(|record| record.version)
+        .max()
+        .ok_or_else(|| {
+            NoAnnotationFound {
+                class: T::class(),
+                name: name.to_owned(),
+                version: requirement.to_owned(),
+            }
+            .into()
+        })
+    }
+    /// Point `tag` of `name` at `version`, anything accepted by `load`, moving it if it already
+    /// points elsewhere. A tag whose version is deleted no longer resolves.
+    fn tag<T: Model>(&self, name: &str, tag: &str, version: &str) -> Result<(), OrcaError> {
+        validate_tag(tag)?;
+        let model = self.load::<T>(name, version)?;
+        self.append_tag_move::<T>(
+            name,
+            tag,
+            &TagMove::now(Some(model.annotation().version.clone())),
+        )
+    }
+    /// Remove `tag` of `name`, keeping its history.
+    fn untag<T: Model>(&self, name: &str, tag: &str) -> Result<(), OrcaError> {
+        if !self.tags::<T>(name)?.contains_key(tag) {
+            return Err(NoAnnotationFound {
+                class: T::class(),
+                name: name.to_owned(),
+                version: tag.to_owned(),
+            }
+            .into());
+        }
+        self.append_tag_move::<T>(name, tag, &TagMove::now(None))
+    }
+    /// Version every current tag of `name` points at.
+    fn tags<T: Model>(&self, name: &str) -> Result<BTreeMap<String, Version>, OrcaError> {
+        Ok(self
+            .tag_log::<T>(name)?
+            .into_iter()
+            .filter_map(|(tag, moves)| Some((tag, moves.last()?.version.clone()?)))
+            .collect())
+    }
+    /// Every move of `tag` of `name`, oldest first.
+    fn tag_history<T: Model>(&self, name: &str, tag: &str) -> Result<Vec<TagMove>, OrcaError> {
+        Ok(self.tag_log::<T>(name)?.remove(tag).unwrap_or_default())
+    }
+    /// Record a move of `tag`. Moves of the same tag are serialized so that none get lost and the
+    /// last one recorded is where the tag points.
+    fn append_tag_move<T: Model>(
+        &self,
+        name: &str,
+        tag: &str,
+        tag_move: &TagMove,
+    ) -> Result<(), OrcaError>;
+    /// Every tag of `name`, removed ones included, with its moves oldest first.
+    fn tag_log<T: Model>(&self, name: &str) -> Result<BTreeMap<String, Vec<TagMove>>, OrcaError>;
+    /// Load by full hash or a unique prefix of it, along with every annotation pointing at it
+    /// sorted by name and version. The returned model carries the first of those annotations.
+    fn load_by_hash<T: Model>(&self, hash_prefix: &str) -> Result<(T, Vec<Annotation>), OrcaError>;
+    /// Rehash every stored spec with `algorithm`, bare digests from before hashes were prefixed
+    /// included, and point references at the new hashes. Returns the old hash of every spec that
+    /// changed along with its new one.
+    ///
+    /// Specs are re-saved one annotation at a time, so nothing else should write to the store
+    /// while this runs.
+    fn migrate_hashes(
+        &self,
+        algorithm: HashAlgorithm,
+    ) -> Result<BTreeMap<String, String>, OrcaError> {
+        rehash_all(self, Some(algorithm))
+    }
+    /// Rewrite every stored spec of a class at its current schema version, running the migrations
+    /// of `Model::migrations` on the way. Specs keep their hash as long as the upgraded content
+    /// still hashes to it. Otherwise they are re-saved under a new hash, with the same algorithm,
+    /// which is added to `renamed` and replaced in the specs that are migrated afterwards.
+    ///
+    /// Like `migrate_hashes`, nothing else should write to the store while this runs.
+    fn migrate_specs<T: Model>(
+        &self,
+        renamed: &mut BTreeMap<String, String>,
+    ) -> Result<(), OrcaError> {
+        rehash_class::<T, _>(self, None, renamed)
+    }
+    /// `migrate_specs` for every class in the store. Returns the old hash of every spec that
+    /// changed along with its new one.
+    fn migrate_schema(&self) -> Result<BTreeMap<String, String>, OrcaError> {
+        rehash_all(self, None)
+    }
+    /// Check every stored spec and annotation of a class. Problems found are reported rather
+    /// than raised, errors are only for failing to read the store at all.
+    fn verify<T: Model>(&self) -> Result<VerifyReport, OrcaError>;
+}
+
+/// Documents are checked against the models they reference in the store, which must be there.
+impl<S: Store> Resolver for S {
+    fn by_hash<T: Model>(&self, hash: &str) -> Result<Option<T>, OrcaError> {
+        Ok(Some(self.load_by_hash::<T>(hash)?.0))
+    }
+    fn by_annotation<T: Model>(&self, name: &str, version: &str) -> Result<Option<T>, OrcaError> {
+        Ok(Some(self.load::<T>(name, version)?))
+    }
+}
+
+/// One move of a tag, see `Store::tag`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TagMove {
+    /// `None` once the tag was removed.
+    pub version: Option<Version>,
+    /// Seconds since the Unix epoch.
+    pub moved_at: u64,
+}
+
+impl TagMove {
+    fn now(version: Option<Version>) -> Self {
+        Self {
+            version,
+            moved_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+        }
+    }
+}
+
+/// Tags follow the annotation name rules and must not read as a version requirement, so that
+/// `Store::load` can tell them apart.
+pub fn validate_tag(tag: &str) -> Result<(), InvalidTag> {
+    Annotation::validate_name(tag).map_err(|error| InvalidTag {
+        tag: tag.to_owned(),
+        rule: error.rule,
+    })?;
+    if VersionReq::parse(tag).is_ok() {
+        return Err(InvalidTag {
+            tag: tag.to_owned(),
+            rule: "must not read as a version requirement".to_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// What `Store::delete` does about models that reference the one being deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Refuse with a `StillReferenced` error listing the referrers.
+    Restrict,
+    /// Delete the referrers too, and theirs in turn.
+    Cascade,
+}
+
+/// Every annotation of a class that can reference others whose spec has a reference matching
+/// `is_dangling`.
+fn referrers_matching<S: Store + ?Sized>(
+    store: &S,
+    is_dangling: &impl Fn(&Reference) -> bool,
+) -> Result<Vec<(String, Record)>, OrcaError> {
+    let mut referrers = referrers_in::<PodJob, _>(store, is_dangling)?;
+    referrers.extend(referrers_in::<PodResult, _>(store, is_dangling)?);
+    referrers.extend(referrers_in::<Pipeline, _>(store, is_dangling)?);
+    Ok(referrers)
+}
+
+/// Every annotation whose spec references the `class` spec `hash` by hash.
+pub(crate) fn hash_referrers<S: Store + ?Sized>(
+    store: &S,
+    class: &str,
+    hash: &str,
+) -> Result<Vec<(String, Record)>, OrcaError> {
+    referrers_matching(store, &|reference: &Reference| {
+        matches!(
+            reference,
+            Reference::Hash {
+                class: referenced_class,
+                hash: referenced_hash,
+            } if referenced_class == class && referenced_hash == hash
+        )
+    })
+}
+
+/// Every annotation of a `R` whose spec has a reference matching `is_dangling`.
+fn referrers_in<R: Model, S: Store + ?Sized>(
+    store: &S,
+    is_dangling: &impl Fn(&Reference) -> bool,
+) -> Result<Vec<(String, Record)>, OrcaError> {
+    let records = store.list::<R>(&ListFilter::default())?;
+    let mut referring = BTreeSet::new();
+    for hash in records.iter().map(|record| &record.hash) {
+        if !referring.contains(hash)
+            && store
+                .load_by_hash::<R>(hash)?
+                .0
+                .references()
+                .iter()
+                .any(is_dangling)
+        {
+            referring.insert(hash.clone());
+        }
+    }
+    Ok(records
+        .into_iter()
+        .filter(|record| referring.contains(&record.hash))
+        .map(|record| (R::class(), record))
+        .collect())
+}
+
+/// Cascade `Store::delete` to a referrer found by `Store::referrers`.
+fn delete_referrer<S: Store + ?Sized>(
+    store: &S,
+    class: &str,
+    record: &Record,
+) -> Result<(), OrcaError> {
+    let version = record.version.to_string();
+    if class == PodJob::class() {
+        store.delete::<PodJob>(&record.name, &version, DeleteMode::Cascade)
+    } else if class == PodResult::class() {
+        store.delete::<PodResult>(&record.name, &version, DeleteMode::Cascade)
+    } else if class == Pipeline::class() {
+        store.delete::<Pipeline>(&record.name, &version, DeleteMode::Cascade)
+    } else {
+        Ok(())
+    }
+}
+
+/// `Store::migrate_hashes` or `Store::migrate_schema` for every class.
+fn rehash_all<S: Store + ?Sized>(
+    store: &S,
+    algorithm: Option<HashAlgorithm>,
+) -> Result<BTreeMap<String, String>, OrcaError> {
+    let mut renamed = BTreeMap::new();
+    // referenced classes go first so that their referrers pick up the new hashes
+    rehash_class::<Pod, _>(store, algorithm, &mut renamed)?;
+    rehash_class::<PodJob, _>(store, algorithm, &mut renamed)?;
+    rehash_class::<Pipeline, _>(store, algorithm, &mut renamed)?;
+    rehash_class::<PodResult, _>(store, algorithm, &mut renamed)?;
+    Ok(renamed)
+}
+
+/// Rehash a single class, adding the hashes it changes to `renamed`. Without an `algorithm` each
+/// spec keeps the one of its current hash.
+fn rehash_class<T: Model, S: Store + ?Sized>(
+    store: &S,
+    algorithm: Option<HashAlgorithm>,
+    renamed: &mut BTreeMap<String, String>,
+) -> Result<(), OrcaError> {
+    let hashes = store
+        .list::<T>(&ListFilter::default())?
+        .into_iter()
+        .map(|record| record.hash)
+        .collect::<BTreeSet<_>>();
+    for old_hash in hashes {
+        let new_hash = rehash_spec::<T, S>(store, &old_hash, algorithm, renamed)?;
+        if new_hash != old_hash {
+            renamed.insert(old_hash, new_hash);
+        }
+    }
+    Ok(())
+}
+
+/// Rewrite the spec stored under `old_hash` in the current schema, in place if its hash holds or
+/// otherwise along with every annotation of it under the new hash.
+fn rehash_spec<T: Model, S: Store + ?Sized>(
+    store: &S,
+    old_hash: &str,
+    algorithm: Option<HashAlgorithm>,
+    renamed: &BTreeMap<String, String>,
+) -> Result<String, OrcaError> {
+    let (model, annotations) = store.load_by_hash::<T>(old_hash)?;
+    let new_algorithm = match algorithm {
+        Some(chosen) => chosen,
+        None => old_hash.parse::<Multihash>()?.algorithm,
+    };
+    let mut spec = serde_json::to_value(&model)?;
+    rename_hashes(&mut spec, renamed);
+    let new_hash = Multihash::compute(
+        new_algorithm,
+        &canonical_spec_json(spec.clone(), &T::class())?,
+    )
+    .to_string();
+    if new_hash == old_hash {
+        store.replace_spec(&model)?;
+        return Ok(new_hash);
+    }
+
+    annotations.iter().try_for_each(|annotation| {
+        let mut annotated = spec.clone();
+        if let JsonValue::Object(fields) = &mut annotated {
+            fields.insert("annotation".to_owned(), serde_json::to_value(annotation)?);
+            fields.insert("hash".to_owned(), JsonValue::from(new_hash.clone()));
+        }
+        let rehashed: T = serde_json::from_value(annotated)?;
+        store.delete_unchecked::<T>(&annotation.name, &annotation.version.to_string())?;
+        store.save_hashed(&rehashed)
+    })?;
+    Ok(new_hash)
+}
+
+/// Replace every string in a serialized spec that is an old hash in `renamed`.
+fn rename_hashes(value: &mut JsonValue, renamed: &BTreeMap<String, String>) {
+    match value {
+        JsonValue::String(text) => {
+            if let Some(new_hash) = renamed.get(text.as_str()) {
+                text.clone_from(new_hash);
+            }
+        }
+        JsonValue::Array(items) => items
+            .iter_mut()
+            .for_each(|item| rename_hashes(item, renamed)),
+        JsonValue::Object(fields) => fields
+            .values_mut()
+            .for_each(|field| rename_hashes(field, renamed)),
+        JsonValue::Null | JsonValue::Bool(_) | JsonValue::Number(_) => {}
+    }
+}
+
+/// Whether `version` satisfies `requirement` in any form accepted by `Store::load`.
+fn version_matches(requirement: &str, version: &Version) -> bool {
+    Version::parse(requirement).map_or_else(
+        |_| {
+            if requirement == "latest" {
+                VersionReq::STAR.matches(version)
+            } else {
+                VersionReq::parse(requirement).is_ok_and(|version_req| version_req.matches(version))
+            }
+        },
+        |exact| exact == *version,
+    )
+}
+
+/// Outcome of `Store::verify`, empty when the store is consistent.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Specs whose content no longer hashes to the hash they are stored under.
+    pub hash_mismatches: Vec<HashMismatch>,
+    /// Annotations pointing at a spec that doesn't exist.
+    pub dangling_annotations: Vec<Record>,
+    /// Hashes of specs that no annotation points at.
+    pub orphaned_specs: Vec<String>,
+    pub unparsable: Vec<Unparsable>,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashMismatch {
+    pub expected: String,
+    pub actual: String,
+}
+
+/// An entry that couldn't be read back, `location` being a path or key depending on the store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unparsable {
+    pub location: String,
+    pub reason: String,
+}
+
+/// Raw annotation or spec as read by a store, for `verify_entries`.
+pub(crate) struct StoredEntry {
+    pub location: String,
+    pub hash: String,
+    pub content: Result<String, OrcaError>,
+    pub format: SpecFormat,
+}
+
+/// Parse a spec on its own, with a stand-in annotation since orphans have none to pair with.
+pub(crate) fn parse_spec<T: Model>(
+    spec: &str,
+    hash: &str,
+    format: SpecFormat,
+) -> Result<T, OrcaError> {
+    let placeholder = serde_yaml::to_string(&Annotation {
+        name: "unannotated".to_owned(),
+        version: Version::new(0, 0, 0),
+        description: String::new(),
+    })?;
+    from_spec::<T>(&placeholder, spec, hash, format)
+}
+
+/// Build the `Store::verify` report shared by all stores from their raw entries.
+pub(crate) fn verify_entries<T: Model>(
+    annotations: Vec<StoredEntry>,
+    specs: Vec<StoredEntry>,
+) -> VerifyReport {
+    let mut unparsable = vec![];
+    let spec_hashes = specs
+        .iter()
+        .map(|spec| spec.hash.clone())
+        .collect::<BTreeSet<_>>();
+    let mut referenced = BTreeSet::new();
+    let mut dangling_annotations = vec![];
+    for entry in annotations {
+        match entry
+            .content
+            .and_then(|yaml| Ok(serde_yaml::from_str::<Annotation>(&yaml)?))
+        {
+            Ok(annotation) if !spec_hashes.contains(&entry.hash) => {
+                dangling_annotations.push(Record {
+                    name: annotation.name,
+                    version: annotation.version,
+                    hash: entry.hash,
+                    description: annotation.description,
+                });
+            }
+            Ok(_) => {
+                referenced.insert(entry.hash);
+            }
+            Err(error) => unparsable.push(Unparsable {
+                location: entry.location,
+                reason: error.to_string(),
+            }),
+        }
+    }
+
+    let mut hash_mismatches = vec![];
+    let mut orphaned_specs = vec![];
+    for entry in specs {
+        if !referenced.contains(&entry.hash) {
+            orphaned_specs.push(entry.hash.clone());
+        }
+        // recompute with the algorithm the spec was stored under, whatever the current default
+        match entry.content.and_then(|content| {
+            let expected = entry.hash.parse::<Multihash>()?;
+            let spec = parse_spec::<T>(&content, &entry.hash, entry.format)?;
+            let actual = Multihash::compute(expected.algorithm, &to_canonical_json::<T>(&spec)?);
+            Ok((expected, actual))
+        }) {
+            Ok((expected, actual)) if actual != expected => hash_mismatches.push(HashMismatch {
+                expected: entry.hash,
+                actual: actual.to_string(),
+            }),
+            Ok(_) => {}
+            Err(error) => unparsable.push(Unparsable {
+                location: entry.location,
+                reason: error.to_string(),
+            }),
+        }
+    }
+
+    VerifyReport {
+        hash_mismatches,
+        dangling_annotations,
+        orphaned_specs,
+        unparsable,
+    }
+}
+
+/// One stored annotation along with the hash of the spec it points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub name: String,
+    pub version: Version,
+    pub hash: String,
+    pub description: String,
+}
+
+/// Narrows down `Store::list`. Fields left as `None` match everything.
+#[derive(Debug, Default, Clone)]
+pub struct ListFilter {
+    pub name: Option<Pattern>,
+    pub version: Option<VersionReq>,
+    pub hash_prefix: Option<String>,
+}
+
+impl ListFilter {
+    pub fn matches(&self, record: &Record) -> bool {
+        self.name
+            .as_ref()
+            .is_none_or(|pattern| pattern.matches(&record.name))
+            && self
+                .version
+                .as_ref()
+                .is_none_or(|version_req| version_req.matches(&record.version))
+            && self
+                .hash_prefix
+                .as_ref()
+                .is_none_or(|prefix| matches_hash_prefix(&record.hash, prefix))
+    }
+}
+
+/// Whether `hash` starts with `prefix`, which may leave out the algorithm, e.g. `FEE2392F` for
+/// `sha256:FEE2392F...`.
+pub(crate) fn matches_hash_prefix(hash: &str, prefix: &str) -> bool {
+    hash.starts_with(prefix)
+        || hash
+            .split_once(':')
+            .is_some_and(|(_, digest)| digest.starts_with(prefix))
+}
+
+/// Pick the single hash starting with `prefix`, the way git resolves short commit hashes.
+pub(crate) fn resolve_hash_prefix(
+    class: &str,
+    prefix: &str,
+    hashes: impl IntoIterator<Item = String>,
+) -> Result<String, OrcaError> {
+    let mut matches = hashes
+        .into_iter()
+        .filter(|hash| matches_hash_prefix(hash, prefix))
+        .collect::<Vec<_>>();
+    match matches.len() {
+        0 => Err(OrcaError::from(NoSpecFound {
+            class: class.to_owned(),
+            hash: prefix.to_owned(),
+        })),
+        1 => Ok(matches.remove(0)),
+        _ => {
+            matches.sort();
+            Err(OrcaError::from(AmbiguousHash {
+                class: class.to_owned(),
+                prefix: prefix.to_owned(),
+                matches,
+            }))
+        }
+    }
 }
 
 pub mod filestore;
+pub mod memstore;
+#[cfg(feature = "sqlite")]
+pub mod sqlitestore;
@@ -0,0 +1,480 @@
+use crate::{
+    error::{AnnotationExists, NoAnnotationFound, NoSpecFound, OrcaError},
+    model::{from_yaml_str, to_yaml, Annotation, HashAlgorithm, Model, SpecFormat},
+    store::{
+        resolve_hash_prefix, verify_entries, ListFilter, Record, Store, StoredEntry, TagMove,
+        VerifyReport,
+    },
+};
+use rusqlite::{params, Connection, OptionalExtension as _};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::PathBuf,
+};
+
+/// Annotations and specs are kept as the same YAML a `LocalFileStore` would write so that
+/// hashes stay identical across backends.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS spec (
+        class TEXT NOT NULL,
+        hash TEXT NOT NULL,
+        yaml TEXT NOT NULL,
+        PRIMARY KEY (class, hash)
+    );
+    CREATE TABLE IF NOT EXISTS annotation (
+        class TEXT NOT NULL,
+        name TEXT NOT NULL,
+        version TEXT NOT NULL,
+        hash TEXT NOT NULL,
+        yaml TEXT NOT NULL,
+        PRIMARY KEY (class, name, version),
+        FOREIGN KEY (class, hash) REFERENCES spec (class, hash)
+    );
+    DROP INDEX IF EXISTS annotation_name;
+    CREATE INDEX IF NOT EXISTS annotation_version ON annotation (class, version);
+    CREATE INDEX IF NOT EXISTS annotation_hash ON annotation (class, hash);
+    CREATE TABLE IF NOT EXISTS tag_move (
+        class TEXT NOT NULL,
+        name TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        sequence INTEGER NOT NULL,
+        version TEXT,
+        moved_at INTEGER NOT NULL,
+        PRIMARY KEY (class, name, tag, sequence)
+    );
+";
+
+/// Number of annotations fetched per query while streaming a listing.
+const PAGE_SIZE: usize = 1_000;
+
+#[derive(Debug)]
+pub struct SqliteStore {
+    pub path: PathBuf,
+    connection: Connection,
+    hash_algorithm: Option<HashAlgorithm>,
+}
+
+impl Store for SqliteStore {
+    fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        self.hash_algorithm
+    }
+
+    fn save_hashed<T: Model>(&self, model: &T) -> Result<(), OrcaError> {
+        let class = T::class();
+        let annotation = model.annotation();
+        annotation.validate()?;
+        let version = annotation.version.to_string();
+        let transaction = self.connection.unchecked_transaction()?;
+
+        if transaction
+            .query_row(
+                "SELECT 1 FROM annotation WHERE class = ?1 AND name = ?2 AND version = ?3",
+                params![class, annotation.name, version],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some()
+        {
+            return Err(OrcaError::from(AnnotationExists {
+                class,
+                name: annotation.name.clone(),
+                version,
+            }));
+        }
+        // many annotations may share a single spec so only the first save writes it
+        transaction.execute(
+            "INSERT OR IGNORE INTO spec (class, hash, yaml) VALUES (?1, ?2, ?3)",
+            params![class, model.hash(), to_yaml::<T>(model)?],
+        )?;
+        transaction.execute(
+            "INSERT INTO annotation (class, name, version, hash, yaml) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                class,
+                annotation.name,
+                version,
+                model.hash(),
+                serde_yaml::to_string(annotation)?
+            ],
+        )?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn load<T: Model>(&self, name: &str, version: &str) -> Result<T, OrcaError> {
+        let class = T::class();
+        let resolved = self.resolve_version::<T>(name, version)?.to_string();
+
+        let (hash, annotation_yaml, spec_yaml): (String, String, String) = self
+            .connection
+            .query_row(
+                "SELECT annotation.hash, annotation.yaml, spec.yaml
+                FROM annotation JOIN spec USING (class, hash)
+                WHERE class = ?1 AND name = ?2 AND version = ?3",
+                params![class, name, resolved],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?
+            .ok_or_else(|| NoAnnotationFound {
+                class,
+                name: name.to_owned(),
+                version: version.to_owned(),
+            })?;
+
+        from_yaml_str::<T>(&annotation_yaml, &spec_yaml, &hash)
+    }
+
+    fn list_iter<T: Model>(
+        &self,
+        filter: &ListFilter,
+    ) -> Result<impl Iterator<Item = Result<Record, OrcaError>> + '_, OrcaError> {
+        Ok(Records {
+            store: self,
+            class: T::class(),
+            filter: filter.clone(),
+            last: (String::new(), String::new()),
+            page: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    fn delete_unchecked<T: Model>(&self, name: &str, version: &str) -> Result<(), OrcaError> {
+        let class = T::class();
+        let transaction = self.connection.unchecked_transaction()?;
+
+        let hash = transaction
+            .query_row(
+                "DELETE FROM annotation WHERE class = ?1 AND name = ?2 AND version = ?3
+                RETURNING hash",
+                params![class, name, version],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .ok_or_else(|| NoAnnotationFound {
+                class: class.clone(),
+                name: name.to_owned(),
+                version: version.to_owned(),
+            })?;
+        transaction.execute(
+            "DELETE FROM spec WHERE class = ?1 AND hash = ?2
+            AND NOT EXISTS (SELECT 1 FROM annotation WHERE class = ?1 AND hash = ?2)",
+            params![class, hash],
+        )?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn save_annotation<T: Model>(
+        &self,
+        hash: &str,
+        annotation: &Annotation,
+    ) -> Result<(), OrcaError> {
+        let class = T::class();
+        annotation.validate()?;
+        let version = annotation.version.to_string();
+        let transaction = self.connection.unchecked_transaction()?;
+
+        if transaction
+            .query_row(
+                "SELECT 1 FROM annotation WHERE class = ?1 AND name = ?2 AND version = ?3",
+                params![class, annotation.name, version],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some()
+        {
+            return Err(OrcaError::from(AnnotationExists {
+                class,
+                name: annotation.name.clone(),
+                version,
+            }));
+        }
+        if transaction
+            .query_row(
+                "SELECT 1 FROM spec WHERE class = ?1 AND hash = ?2",
+                params![class, hash],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_none()
+        {
+            return Err(OrcaError::from(NoSpecFound {
+                class,
+                hash: hash.to_owned(),
+            }));
+        }
+        transaction.execute(
+            "INSERT INTO annotation (class, name, version, hash, yaml) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                class,
+                annotation.name,
+                version,
+                hash,
+                serde_yaml::to_string(annotation)?
+            ],
+        )?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn replace_annotation<T: Model>(&self, annotation: &Annotation) -> Result<(), OrcaError> {
+        let class = T::class();
+        let version = annotation.version.to_string();
+
+        let updated = self.connection.execute(
+            "UPDATE annotation SET yaml = ?4 WHERE class = ?1 AND name = ?2 AND version = ?3",
+            params![
+                class,
+                annotation.name,
+                version,
+                serde_yaml::to_string(annotation)?
+            ],
+        )?;
+        if updated == 0 {
+            return Err(OrcaError::from(NoAnnotationFound {
+                class,
+                name: annotation.name.clone(),
+                version,
+            }));
+        }
+
+        Ok(())
+    }
+
+    fn replace_spec<T: Model>(&self, model: &T) -> Result<(), OrcaError> {
+        let class = T::class();
+
+        let updated = self.connection.execute(
+            "UPDATE spec SET yaml = ?3 WHERE class = ?1 AND hash = ?2",
+            params![class, model.hash(), to_yaml::<T>(model)?],
+        )?;
+        if updated == 0 {
+            return Err(OrcaError::from(NoSpecFound {
+                class,
+                hash: model.hash().to_owned(),
+            }));
+        }
+
+        Ok(())
+    }
+
+    fn append_tag_move<T: Model>(
+        &self,
+        name: &str,
+        tag: &str,
+        tag_move: &TagMove,
+    ) -> Result<(), OrcaError> {
+        // a single statement so concurrent moves of the same tag can't claim the same sequence
+        self.connection.execute(
+            "INSERT INTO tag_move (class, name, tag, sequence, version, moved_at)
+            SELECT ?1, ?2, ?3, COALESCE(MAX(sequence), 0) + 1, ?4, ?5
+            FROM tag_move WHERE class = ?1 AND name = ?2 AND tag = ?3",
+            params![
+                T::class(),
+                name,
+                tag,
+                tag_move.version.as_ref().map(ToString::to_string),
+                tag_move.moved_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn tag_log<T: Model>(&self, name: &str) -> Result<BTreeMap<String, Vec<TagMove>>, OrcaError> {
+        let mut tag_log = BTreeMap::<String, Vec<TagMove>>::new();
+        for row in self
+            .connection
+            .prepare(
+                "SELECT tag, version, moved_at FROM tag_move WHERE class = ?1 AND name = ?2
+                ORDER BY tag, sequence",
+            )?
+            .query_map(params![T::class(), name], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get(2)?,
+                ))
+            })?
+        {
+            let (tag, version, moved_at) = row?;
+            tag_log.entry(tag).or_default().push(TagMove {
+                version: version.map(|found| found.parse()).transpose()?,
+                moved_at,
+            });
+        }
+        Ok(tag_log)
+    }
+
+    fn load_by_hash<T: Model>(&self, hash_prefix: &str) -> Result<(T, Vec<Annotation>), OrcaError> {
+        let class = T::class();
+
+        let hash = resolve_hash_prefix(
+            &class,
+            hash_prefix,
+            self.connection
+                .prepare(
+                    "SELECT hash FROM spec WHERE class = ?1 AND (
+                        substr(hash, 1, length(?2)) = ?2
+                        OR substr(hash, instr(hash, ':') + 1, length(?2)) = ?2
+                    )",
+                )?
+                .query_map(params![class, hash_prefix], |row| row.get(0))?
+                .collect::<Result<Vec<String>, _>>()?,
+        )?;
+        let annotation_yamls = self
+            .connection
+            .prepare(
+                "SELECT yaml FROM annotation WHERE class = ?1 AND hash = ?2
+                ORDER BY name, version",
+            )?
+            .query_map(params![class, hash], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+        let annotations = annotation_yamls
+            .iter()
+            .map(|annotation_yaml| serde_yaml::from_str(annotation_yaml))
+            .collect::<Result<Vec<Annotation>, _>>()?;
+
+        let first_annotation_yaml = annotation_yamls.first().ok_or_else(|| NoAnnotationFound {
+            class: class.clone(),
+            name: hash.clone(),
+            version: "*".to_owned(),
+        })?;
+        let spec_yaml: String = self.connection.query_row(
+            "SELECT yaml FROM spec WHERE class = ?1 AND hash = ?2",
+            params![class, hash],
+            |row| row.get(0),
+        )?;
+        Ok((
+            from_yaml_str::<T>(first_annotation_yaml, &spec_yaml, &hash)?,
+            annotations,
+        ))
+    }
+
+    fn verify<T: Model>(&self) -> Result<VerifyReport, OrcaError> {
+        let class = T::class();
+        let annotations = self
+            .connection
+            .prepare("SELECT name, version, hash, yaml FROM annotation WHERE class = ?1")?
+            .query_map(params![class], |row| {
+                Ok(StoredEntry {
+                    location: format!("{}:{}", row.get::<_, String>(0)?, row.get::<_, String>(1)?),
+                    hash: row.get(2)?,
+                    content: Ok(row.get(3)?),
+                    format: SpecFormat::Yaml,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let specs = self
+            .connection
+            .prepare("SELECT hash, yaml FROM spec WHERE class = ?1")?
+            .query_map(params![class], |row| {
+                Ok(StoredEntry {
+                    location: row.get(0)?,
+                    hash: row.get(0)?,
+                    content: Ok(row.get(1)?),
+                    format: SpecFormat::Yaml,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(verify_entries::<T>(annotations, specs))
+    }
+}
+
+impl SqliteStore {
+    /// Open (or create) the database at `path`. Use `:memory:` for a throwaway store.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self, OrcaError> {
+        let store_path = path.into();
+        let connection = Connection::open(&store_path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            path: store_path,
+            connection,
+            hash_algorithm: None,
+        })
+    }
+
+    /// Same store, rehashing models saved to it with `algorithm`.
+    #[must_use]
+    pub const fn with_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = Some(algorithm);
+        self
+    }
+}
+
+/// Streams a listing by paging through annotations ordered by `(name, version)`.
+struct Records<'store> {
+    store: &'store SqliteStore,
+    class: String,
+    filter: ListFilter,
+    last: (String, String), // `(name, version)` of the last fetched row
+    page: VecDeque<Result<Record, OrcaError>>,
+    done: bool,
+}
+
+impl Iterator for Records<'_> {
+    type Item = Result<Record, OrcaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.page.is_empty() && !self.done {
+            let fetched = self.fetch_page();
+            self.done |= fetched.is_err();
+            self.page.extend(fetched.map_or_else(
+                |error| vec![Err(error)],
+                |records| records.into_iter().map(Ok).collect(),
+            ));
+        }
+        self.page.pop_front()
+    }
+}
+
+impl Records<'_> {
+    /// Names are matched by `ListFilter::matches` rather than SQL `GLOB`, which differs from
+    /// `glob::Pattern` in its syntax, e.g. `[^a]` instead of `[!a]`.
+    fn fetch_page(&mut self) -> Result<Vec<Record>, OrcaError> {
+        let hash_prefix = self.filter.hash_prefix.as_deref().unwrap_or_default();
+        let records = self
+            .store
+            .connection
+            .prepare_cached(
+                "SELECT name, version, hash, yaml FROM annotation
+                WHERE class = ?1 AND (name, version) > (?2, ?3) AND (
+                    substr(hash, 1, length(?4)) = ?4
+                    OR substr(hash, instr(hash, ':') + 1, length(?4)) = ?4
+                )
+                ORDER BY name, version LIMIT ?5",
+            )?
+            .query_map(
+                params![self.class, self.last.0, self.last.1, hash_prefix, PAGE_SIZE],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                },
+            )?
+            .map(|row| {
+                let (name, version, hash, annotation_yaml) = row?;
+                let annotation: Annotation = serde_yaml::from_str(&annotation_yaml)?;
+                Ok(Record {
+                    name,
+                    version: version.parse()?,
+                    hash,
+                    description: annotation.description,
+                })
+            })
+            .collect::<Result<Vec<_>, OrcaError>>()?;
+
+        self.done = records.len() < PAGE_SIZE;
+        if let Some(last) = records.last() {
+            self.last = (last.name.clone(), last.version.to_string());
+        }
+        Ok(records
+            .into_iter()
+            .filter(|record| self.filter.matches(record))
+            .collect())
+    }
+}
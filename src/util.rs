@@ -1,5 +1,13 @@
-use sha2::{Digest, Sha256};
-use std::any::type_name;
+#[cfg(feature = "color")]
+use crate::error::should_color;
+use crate::model::HashAlgorithm;
+#[cfg(feature = "color")]
+use colored::{ColoredString, Colorize};
+use sha2::{Digest as _, Sha256};
+use std::{
+    any::type_name,
+    path::{Path, PathBuf},
+};
 
 #[expect(
     clippy::unwrap_used,
@@ -8,13 +16,69 @@ use std::any::type_name;
 pub fn get_type_name<T>() -> String {
     type_name::<T>()
         .split("::")
-        .map(str::to_string)
+        .map(str::to_owned)
         .collect::<Vec<String>>()
         .last()
         .unwrap()
         .to_lowercase()
 }
 
-pub fn hash(buffer: &str) -> String {
-    format!("{:X}", Sha256::digest(buffer))
+/// Uppercase hex digest of `buffer`, see [`crate::model::Multihash`].
+pub fn hash(algorithm: HashAlgorithm, buffer: &str) -> String {
+    match algorithm {
+        HashAlgorithm::Sha256 => format!("{:X}", Sha256::digest(buffer)),
+        #[cfg(feature = "blake3")]
+        HashAlgorithm::Blake3 => blake3::hash(buffer.as_bytes())
+            .to_hex()
+            .to_ascii_uppercase(),
+    }
+}
+
+/// Lexically drop repeated separators, trailing separators and `.` past the start, without
+/// touching the filesystem.
+pub fn normalize_path(path: impl AsRef<Path>) -> PathBuf {
+    path.as_ref().components().collect()
+}
+
+/// Highlight text in messages, falling back to plain text when coloring is disabled (see
+/// [`crate::error::ColorChoice`]) or the `color` feature is off.
+pub trait Paint {
+    fn bright_red(&self) -> String;
+    fn bright_cyan(&self) -> String;
+    fn cyan(&self) -> String;
+}
+
+#[cfg(feature = "color")]
+impl Paint for str {
+    fn bright_red(&self) -> String {
+        paint(self, &Colorize::bright_red(self))
+    }
+    fn bright_cyan(&self) -> String {
+        paint(self, &Colorize::bright_cyan(self))
+    }
+    fn cyan(&self) -> String {
+        paint(self, &Colorize::cyan(self))
+    }
+}
+
+#[cfg(feature = "color")]
+fn paint(text: &str, colored_text: &ColoredString) -> String {
+    if should_color() {
+        colored_text.to_string()
+    } else {
+        text.to_owned()
+    }
+}
+
+#[cfg(not(feature = "color"))]
+impl Paint for str {
+    fn bright_red(&self) -> String {
+        self.to_owned()
+    }
+    fn bright_cyan(&self) -> String {
+        self.to_owned()
+    }
+    fn cyan(&self) -> String {
+        self.to_owned()
+    }
 }
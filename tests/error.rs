@@ -0,0 +1,63 @@
+#![expect(clippy::panic_in_result_fn, reason = "Panics OK in tests.")]
+
+pub mod fixture;
+use fixture::{pod_style, store_test};
+use orcapod::{
+    error::{set_color_choice, ColorChoice, ErrorKind, IOFailure, OrcaError},
+    model::Pod,
+    store::Store as _,
+};
+use std::{error::Error, io, path::PathBuf};
+
+#[test]
+fn verify_error_kind() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    store.save(&pod_style()?)?;
+    let error = store
+        .save(&pod_style()?)
+        .err()
+        .ok_or("Saving twice should fail.")?;
+    assert_eq!(error.kind(), ErrorKind::AnnotationExists);
+
+    let missing = store
+        .load::<Pod>("style-transfer", "9.9.9")
+        .err()
+        .ok_or("Loading an unknown version should fail.")?;
+    assert!(matches!(missing, OrcaError::NoAnnotationFound(_)));
+    Ok(())
+}
+
+#[test]
+fn verify_error_source_chain() -> Result<(), Box<dyn Error>> {
+    let error = OrcaError::from(IOFailure {
+        path: PathBuf::from("/data/spec.yaml"),
+        error: io::Error::other("disk unplugged"),
+    });
+    assert_eq!(error.kind(), ErrorKind::IOFailure);
+    assert_eq!(
+        error
+            .source()
+            .ok_or("Failure should chain to the IO error.")?
+            .to_string(),
+        "disk unplugged"
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_error_renders_plain_without_color() {
+    // `Auto` would depend on whether the tests happen to run in a terminal
+    set_color_choice(ColorChoice::Never);
+    let error = OrcaError::from(IOFailure {
+        path: PathBuf::from("/data/spec.yaml"),
+        error: io::Error::other("disk unplugged"),
+    });
+    assert!(
+        !error.to_string().contains('\u{1b}'),
+        "Color is off so no escapes expected."
+    );
+    assert_eq!(
+        error.report().to_string(),
+        "error[IOFailure]: IO Error: disk unplugged at /data/spec.yaml\n  caused by: disk unplugged"
+    );
+}
@@ -1,20 +1,31 @@
 use orcapod::{
-    model::{Annotation, Pod, StreamInfo},
-    store::{filestore::LocalFileStore, Store},
+    error::OrcaError,
+    model::{
+        to_canonical_json, Annotation, Edge, Input, Migration, Model, Multihash, Output, Pipeline,
+        Pod, PodJob, PodRef, PodResult, Port, Status, StreamInfo, Version,
+    },
+    store::{filestore::LocalFileStore, DeleteMode, Store as _},
 };
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
 use std::{collections::BTreeMap, error::Error, fs, ops::Deref, path::PathBuf};
 use tempfile::tempdir;
 
-pub fn pod_style() -> Result<Pod, Box<dyn Error>> {
+pub fn pod_style() -> Result<Pod, OrcaError> {
+    pod_custom("style-transfer", "0.67.0", "tail -f /dev/null")
+}
+
+/// Variant of the style pod, where a different `command` yields a different spec hash.
+pub fn pod_custom(name: &str, version: &str, command: &str) -> Result<Pod, OrcaError> {
     Pod::new(
         Annotation {
-            name: "style-transfer".to_owned(),
+            name: name.to_owned(),
             description: "This is an example pod.".to_owned(),
-            version: "0.67.0".to_owned(),
+            version: version.parse()?,
         },
         "https://github.com/zenml-io/zenml/tree/0.67.0".to_owned(),
         "zenmldocker/zenml-server:0.67.0".to_owned(),
-        "tail -f /dev/null".to_owned(),
+        command.to_owned(),
         BTreeMap::from([
             (
                 "painting".to_owned(),
@@ -45,27 +56,171 @@ pub fn pod_style() -> Result<Pod, Box<dyn Error>> {
     )
 }
 
+pub fn pod_job_style(pod: &Pod) -> Result<PodJob, OrcaError> {
+    PodJob::new(
+        Annotation {
+            name: "style-transfer-job".to_owned(),
+            description: "This is an example pod job.".to_owned(),
+            version: Version::new(0, 1, 0),
+        },
+        pod,
+        BTreeMap::from([
+            (
+                "painting".to_owned(),
+                Input::File(PathBuf::from("/data/starry-night.png")),
+            ),
+            (
+                "image".to_owned(),
+                Input::Blob(
+                    "B3F1D7A2E2C04B6B8B6A7D1C9E5F4A3B2C1D0E9F8A7B6C5D4E3F2A1B0C9D8E7F".to_owned(),
+                ),
+            ),
+        ]),
+        PathBuf::from("/data/output"),
+    )
+}
+
+pub fn pod_result_style(pod_job: &PodJob, pod: &Pod) -> Result<PodResult, OrcaError> {
+    PodResult::new(
+        Annotation {
+            name: "style-transfer-result".to_owned(),
+            description: "This is an example pod result.".to_owned(),
+            version: Version::new(0, 1, 0),
+        },
+        pod_job,
+        pod,
+        Status::Succeeded,
+        0,
+        1_727_000_000,
+        1_727_000_042,
+        BTreeMap::from([(
+            "styled".to_owned(),
+            Output {
+                path: PathBuf::from("/data/output/styled.png"),
+                hash: "6D1B0C9E8F7A6B5C4D3E2F1A0B9C8D7E6F5A4B3C2D1E0F9A8B7C6D5E4F3A2B1C".to_owned(),
+            },
+        )]),
+    )
+}
+
+pub fn port(node: &str, key: &str) -> Port {
+    Port {
+        node: node.to_owned(),
+        key: key.to_owned(),
+    }
+}
+
+/// Two chained style transfers where the first result is restyled with a second painting.
+pub fn pipeline_style(edges: Vec<Edge>) -> Result<Pipeline, OrcaError> {
+    Pipeline::new(
+        Annotation {
+            name: "double-style-transfer".to_owned(),
+            description: "This is an example pipeline.".to_owned(),
+            version: Version::new(0, 1, 0),
+        },
+        BTreeMap::from([
+            (
+                "first".to_owned(),
+                PodRef::Annotation {
+                    name: "style-transfer".to_owned(),
+                    version: "0.67.0".to_owned(),
+                },
+            ),
+            ("second".to_owned(), PodRef::Hash(pod_style()?.hash)),
+        ]),
+        edges,
+        BTreeMap::from([
+            ("image".to_owned(), vec![port("first", "image")]),
+            (
+                "painting".to_owned(),
+                vec![port("second", "painting"), port("first", "painting")],
+            ),
+        ]),
+        |_| pod_style(),
+    )
+}
+
+pub fn pipeline_style_edges() -> Vec<Edge> {
+    vec![Edge {
+        source: port("first", "styled"),
+        target: port("second", "image"),
+    }]
+}
+
+/// Model whose spec changed shape once, schema 1 called `greeting` `message`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Greeter {
+    pub annotation: Annotation,
+    pub hash: String,
+    pub greeting: String,
+}
+
+impl Model for Greeter {
+    fn schema_version() -> u32 {
+        2
+    }
+    fn migrations() -> BTreeMap<u32, Migration> {
+        BTreeMap::from([(1, rename_message as Migration)])
+    }
+    fn hash(&self) -> &str {
+        &self.hash
+    }
+    fn annotation(&self) -> &Annotation {
+        &self.annotation
+    }
+}
+
+#[expect(
+    clippy::unnecessary_wraps,
+    reason = "Signature is fixed by `Migration`."
+)]
+fn rename_message(mut spec: Mapping) -> Result<Mapping, OrcaError> {
+    if let Some(message) = spec.remove("message") {
+        spec.insert(Value::from("greeting"), message);
+    }
+    Ok(spec)
+}
+
+pub fn greeter(greeting: &str) -> Result<Greeter, OrcaError> {
+    let mut greeter = Greeter {
+        annotation: Annotation {
+            name: "greeter".to_owned(),
+            description: "This is an example of a migrated model.".to_owned(),
+            version: Version::new(0, 1, 0),
+        },
+        hash: String::new(),
+        greeting: greeting.to_owned(),
+    };
+    greeter.hash = Multihash::of(&to_canonical_json(&greeter)?).to_string();
+    Ok(greeter)
+}
+
 #[derive(Debug)]
 pub struct TestLocalStore {
     store: LocalFileStore,
 }
 
+impl Deref for TestLocalStore {
+    type Target = LocalFileStore;
+    fn deref(&self) -> &Self::Target {
+        &self.store
+    }
+}
+
 #[expect(
     clippy::expect_used,
     reason = "Required since can't modify drop signature."
 )]
-pub fn store_test(store_directory: Option<&str>) -> Result<TestLocalStore, Box<dyn Error>> {
-    impl Deref for TestLocalStore {
-        type Target = LocalFileStore;
-        fn deref(&self) -> &Self::Target {
-            &self.store
-        }
-    }
-    impl Drop for TestLocalStore {
-        fn drop(&mut self) {
+impl Drop for TestLocalStore {
+    fn drop(&mut self) {
+        // tests that never save leave no directory behind
+        if self.store.directory.exists() {
             fs::remove_dir_all(self.store.directory.as_path()).expect("Failed to teardown store.");
         }
     }
+}
+
+pub fn store_test(store_directory: Option<&str>) -> Result<TestLocalStore, Box<dyn Error>> {
     let tmp_directory = String::from(tempdir()?.path().to_string_lossy());
     let store =
         store_directory.map_or_else(|| LocalFileStore::new(tmp_directory), LocalFileStore::new);
@@ -78,28 +233,34 @@ pub struct TestLocallyStoredPod<'base> {
     pod: Pod,
 }
 
+impl Deref for TestLocallyStoredPod<'_> {
+    type Target = Pod;
+    fn deref(&self) -> &Self::Target {
+        &self.pod
+    }
+}
+
 #[expect(
     clippy::expect_used,
     reason = "Required since can't modify drop signature."
 )]
+impl Drop for TestLocallyStoredPod<'_> {
+    fn drop(&mut self) {
+        self.store
+            .delete::<Pod>(
+                &self.pod.annotation.name,
+                &self.pod.annotation.version.to_string(),
+                DeleteMode::Restrict,
+            )
+            .expect("Failed to teardown pod.");
+    }
+}
+
 pub fn add_pod_storage(
     pod: Pod,
     store: &TestLocalStore,
-) -> Result<TestLocallyStoredPod, Box<dyn Error>> {
-    impl<'base> Deref for TestLocallyStoredPod<'base> {
-        type Target = Pod;
-        fn deref(&self) -> &Self::Target {
-            &self.pod
-        }
-    }
-    impl<'base> Drop for TestLocallyStoredPod<'base> {
-        fn drop(&mut self) {
-            self.store
-                .delete_pod(&self.pod.annotation.name, &self.pod.annotation.version)
-                .expect("Failed to teardown pod.");
-        }
-    }
+) -> Result<TestLocallyStoredPod<'_>, Box<dyn Error>> {
     let pod_with_storage = TestLocallyStoredPod { store, pod };
-    pod_with_storage.store.save_pod(&pod_with_storage)?;
+    pod_with_storage.store.save(&pod_with_storage.pod)?;
     Ok(pod_with_storage)
 }
@@ -0,0 +1,105 @@
+#![cfg(feature = "blake3")]
+#![expect(clippy::panic_in_result_fn, reason = "Panics OK in tests.")]
+
+pub mod fixture;
+use fixture::{pod_job_style, pod_style};
+use orcapod::{
+    model::{HashAlgorithm, Model as _, Multihash, Pod, PodJob, Reference},
+    store::{filestore::LocalFileStore, memstore::InMemoryStore, Store as _},
+};
+use std::error::Error;
+use tempfile::tempdir;
+
+#[test]
+fn verify_hash_algorithm_per_model() -> Result<(), Box<dyn Error>> {
+    let sha256_pod = pod_style()?;
+    let blake3_pod = sha256_pod.with_hash_algorithm(HashAlgorithm::Blake3)?;
+    assert_eq!(
+        (
+            sha256_pod.hash.parse::<Multihash>()?.algorithm,
+            blake3_pod.hash.parse::<Multihash>()?.algorithm,
+        ),
+        (HashAlgorithm::Sha256, HashAlgorithm::Blake3),
+        "Models should hash with SHA-256 unless rehashed."
+    );
+    assert_eq!(
+        blake3_pod.with_hash_algorithm(HashAlgorithm::Sha256)?.hash,
+        sha256_pod.hash,
+        "Rehashing should only depend on the spec."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_store_mixes_hash_algorithms() -> Result<(), Box<dyn Error>> {
+    let store = InMemoryStore::new();
+    let sha256_pod = pod_style()?;
+    let mut blake3_pod = sha256_pod.with_hash_algorithm(HashAlgorithm::Blake3)?;
+    "style-transfer-blake3".clone_into(&mut blake3_pod.annotation.name);
+    store.save(&sha256_pod)?;
+    store.save(&blake3_pod)?;
+    assert!(
+        store.verify::<Pod>()?.is_clean(),
+        "Specs should verify with the algorithm they were stored under."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_store_hash_algorithm() -> Result<(), Box<dyn Error>> {
+    let directory = tempdir()?;
+    let store = LocalFileStore::new(directory.path()).with_hash_algorithm(HashAlgorithm::Blake3);
+    let pod = pod_style()?;
+    store.save(&pod)?;
+    let stored_pod = store.load::<Pod>("style-transfer", "0.67.0")?;
+    store.save(&pod_job_style(&stored_pod)?)?;
+
+    let blake3_hash = pod.with_hash_algorithm(HashAlgorithm::Blake3)?.hash;
+    assert!(
+        directory
+            .path()
+            .join("pod")
+            .join(blake3_hash.replacen(':', "-", 1))
+            .join("spec.yaml")
+            .is_file(),
+        "Pod should be saved under a `blake3-` directory."
+    );
+    assert_eq!(
+        stored_pod.hash, blake3_hash,
+        "Pod should load with the store's algorithm."
+    );
+    assert_eq!(
+        store
+            .load::<PodJob>("style-transfer-job", "0.1.0")?
+            .hash
+            .parse::<Multihash>()?
+            .algorithm,
+        HashAlgorithm::Blake3,
+        "Every model saved should be rehashed."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_hash_migration() -> Result<(), Box<dyn Error>> {
+    let store = InMemoryStore::new();
+    let sha256_pod = pod_style()?;
+    store.save(&sha256_pod)?;
+    store.save(&pod_job_style(&sha256_pod)?)?;
+
+    store.migrate_hashes(HashAlgorithm::Blake3)?;
+    let blake3_hash = sha256_pod.with_hash_algorithm(HashAlgorithm::Blake3)?.hash;
+    let (_, annotations) = store.load_by_hash::<Pod>(&blake3_hash)?;
+    assert_eq!(annotations.len(), 1, "Pod should be rehashed.");
+    assert_eq!(
+        store
+            .load::<PodJob>("style-transfer-job", "0.1.0")?
+            .references(),
+        vec![Reference::Hash {
+            class: "pod".to_owned(),
+            hash: blake3_hash,
+        }],
+        "Job should reference the rehashed pod."
+    );
+    Ok(())
+}
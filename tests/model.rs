@@ -1,16 +1,402 @@
 #![expect(clippy::panic_in_result_fn, reason = "Panics OK in tests.")]
 
-use std::error::Error;
+use std::{collections::BTreeMap, error::Error, path::PathBuf};
 pub mod fixture;
-use fixture::pod_style;
-use indoc::indoc;
-use orcapod::model::{to_yaml, Pod};
+use fixture::{
+    greeter, pipeline_style, pipeline_style_edges, pod_custom, pod_job_style, pod_result_style,
+    pod_style, port, Greeter,
+};
+use indoc::{formatdoc, indoc};
+use orcapod::{
+    error::{ErrorKind, OrcaError},
+    model::{
+        from_yaml_str, to_canonical_json, to_document, to_spec, to_yaml, Annotation, Edge,
+        HashAlgorithm, Input, Model, Multihash, Output, Pipeline, Pod, PodJob, PodRef, PodResult,
+        SpecFormat, Status, Version,
+    },
+    store::{memstore::InMemoryStore, Store as _},
+};
+use serde_json::json;
+use serde_yaml::Value;
 
 #[test]
 fn verify_hash() -> Result<(), Box<dyn Error>> {
     assert_eq!(
         pod_style()?.hash,
-        "13D69656D396C272588DD875B2802FAEE1A56BD985E3C43C7DB276A373BC9DDB"
+        "sha256:FEE2392F679AB826697964930B070373656E9DE5118F0DCC88B1F1A595B90F50"
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_multihash() -> Result<(), Box<dyn Error>> {
+    let hash = pod_style()?.hash;
+    let multihash = hash.parse::<Multihash>()?;
+    assert_eq!(
+        (multihash.algorithm, multihash.to_string()),
+        (HashAlgorithm::Sha256, hash.clone()),
+        "Hash should round trip."
+    );
+    assert_eq!(
+        hash.trim_start_matches("sha256:").parse::<Multihash>()?,
+        multihash,
+        "Bare digest should read as SHA-256."
+    );
+    for invalid in ["md5:0A1B", "sha256:0a1b", "sha256:"] {
+        assert!(
+            invalid.parse::<Multihash>().is_err(),
+            "`{invalid}` should be rejected."
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn verify_pod_canonical_json() -> Result<(), Box<dyn Error>> {
+    assert_eq!(
+        to_canonical_json::<Pod>(&pod_style()?)?,
+        concat!(
+            r#"{"class":"pod","command":"tail -f /dev/null","#,
+            r#""image":"zenmldocker/zenml-server:0.67.0","#,
+            r#""input_stream_map":{"#,
+            r#""image":{"match_pattern":"/input/image.png","path":"/input/image.png"},"#,
+            r#""painting":{"match_pattern":"/input/painting.png","path":"/input/painting.png"}},"#,
+            r#""output_dir":"/output","#,
+            r#""output_stream_map":{"styled":{"match_pattern":"./styled.png","path":"./styled.png"}},"#,
+            r#""recommended_cpus":0.25,"recommended_memory":2147483648,"required_gpu":null,"#,
+            r#""source_commit_url":"https://github.com/zenml-io/zenml/tree/0.67.0"}"#,
+        ),
+        "Canonical encoding is what hashes are computed from and must stay stable."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_hash_ignores_yaml_presentation() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let reformatted: Pod = from_yaml_str(
+        &serde_yaml::to_string(&pod.annotation)?,
+        indoc! {r#"
+            class: pod
+            source_commit_url: "https://github.com/zenml-io/zenml/tree/0.67.0"
+            required_gpu: ~
+            recommended_memory: 0x80000000
+            recommended_cpus: 2.5e-1
+            output_stream_map: {styled: {path: ./styled.png, match_pattern: ./styled.png}}
+            output_dir: '/output'
+            input_stream_map:
+              painting: {match_pattern: /input/painting.png, path: /input/painting.png}
+              image: {match_pattern: /input/image.png, path: /input/image.png}
+            image: zenmldocker/zenml-server:0.67.0
+            command: >-
+              tail -f /dev/null
+        "#},
+        &pod.hash,
+    )?;
+    assert_eq!(
+        to_canonical_json::<Pod>(&reformatted)?,
+        to_canonical_json::<Pod>(&pod)?,
+        "Key order, quoting, number notation and flow style shouldn't change the hash."
+    );
+    Ok(())
+}
+
+fn canonical_json_with_cpus(recommended_cpus: &str) -> Result<String, Box<dyn Error>> {
+    let pod: Pod = from_yaml_str(
+        &serde_yaml::to_string(&pod_style()?.annotation)?,
+        &to_yaml::<Pod>(&pod_style()?)?.replace(
+            "recommended_cpus: 0.25",
+            &format!("recommended_cpus: {recommended_cpus}"),
+        ),
+        "",
+    )?;
+    Ok(to_canonical_json::<Pod>(&pod)?)
+}
+
+#[test]
+fn verify_float_hashes_shortest_form() -> Result<(), Box<dyn Error>> {
+    assert_eq!(
+        canonical_json_with_cpus("0.1")?,
+        to_canonical_json::<Pod>(&pod_style()?)?
+            .replace(r#""recommended_cpus":0.25,"#, r#""recommended_cpus":0.1,"#),
+        "An `f32` should hash as written, not widened to `f64`."
+    );
+    assert_eq!(
+        to_canonical_json(&json!({ "cpus": 0.100_000_001_490_116_12_f64 }))?,
+        r#"{"class":"value","cpus":0.10000000149011612}"#,
+        "An `f64` should keep its full precision."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_negative_zero_hashes_as_zero() -> Result<(), Box<dyn Error>> {
+    assert_eq!(
+        canonical_json_with_cpus("-0.0")?,
+        to_canonical_json::<Pod>(&pod_style()?)?
+            .replace(r#""recommended_cpus":0.25,"#, r#""recommended_cpus":0,"#),
+        "`-0.0` should hash the same as `0`."
+    );
+    assert_eq!(
+        canonical_json_with_cpus("-0.0")?,
+        canonical_json_with_cpus("0.0")?,
+        "`-0.0` should hash the same as `0.0`."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_hash_ignores_trailing_separators() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let pod_job = pod_job_style(&pod)?;
+    let pod_job_slashed = PodJob::new(
+        Annotation {
+            name: "style-transfer-job".to_owned(),
+            description: "This is an example pod job.".to_owned(),
+            version: Version::new(0, 1, 0),
+        },
+        &pod,
+        BTreeMap::from([
+            (
+                "painting".to_owned(),
+                Input::File(PathBuf::from("/data/./starry-night.png")),
+            ),
+            (
+                "image".to_owned(),
+                Input::Blob(
+                    "B3F1D7A2E2C04B6B8B6A7D1C9E5F4A3B2C1D0E9F8A7B6C5D4E3F2A1B0C9D8E7F".to_owned(),
+                ),
+            ),
+        ]),
+        PathBuf::from("/data/output/"),
+    )?;
+    assert_eq!(
+        pod_job_slashed.hash, pod_job.hash,
+        "Equivalent job paths should hash the same."
+    );
+    let pod_result = pod_result_style(&pod_job, &pod)?;
+    let pod_result_slashed = PodResult::new(
+        Annotation {
+            name: "style-transfer-result".to_owned(),
+            description: "This is an example pod result.".to_owned(),
+            version: Version::new(0, 1, 0),
+        },
+        &pod_job,
+        &pod,
+        Status::Succeeded,
+        0,
+        1_727_000_000,
+        1_727_000_042,
+        BTreeMap::from([(
+            "styled".to_owned(),
+            Output {
+                path: PathBuf::from("/data/output//styled.png/"),
+                hash: "6D1B0C9E8F7A6B5C4D3E2F1A0B9C8D7E6F5A4B3C2D1E0F9A8B7C6D5E4F3A2B1C".to_owned(),
+            },
+        )]),
+    )?;
+    assert_eq!(
+        pod_result_slashed.hash, pod_result.hash,
+        "Equivalent result paths should hash the same."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_spec_schema_upgrade() -> Result<(), Box<dyn Error>> {
+    let current = greeter("hello")?;
+    assert_eq!(
+        to_yaml::<Greeter>(&current)?,
+        "class: greeter\nschema_version: 2\ngreeting: hello\n",
+        "Header should carry the current schema version."
+    );
+    let annotation_yaml = serde_yaml::to_string(&current.annotation)?;
+    let upgraded: Greeter = from_yaml_str(
+        &annotation_yaml,
+        "class: greeter\nschema_version: 1\nmessage: hello\n",
+        &current.hash,
+    )?;
+    assert_eq!(
+        (upgraded.hash, upgraded.greeting),
+        (current.hash.clone(), current.greeting),
+        "Schema 1 spec should be migrated on read."
+    );
+
+    let newer = from_yaml_str::<Greeter>(
+        &annotation_yaml,
+        "class: greeter\nschema_version: 3\ngreeting: hello\n",
+        &current.hash,
+    )
+    .err()
+    .ok_or("Spec from a newer schema should be rejected.")?;
+    assert!(
+        newer.kind() == ErrorKind::UnsupportedSchemaVersion,
+        "Unexpected error: {newer}"
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_pod_document() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let document = to_document(&pod, SpecFormat::Yaml)?;
+    assert_eq!(
+        Pod::from_spec_str(&document, SpecFormat::Yaml)?.hash,
+        pod.hash,
+        "YAML document should round trip."
+    );
+    let undeclared = document.replace(&format!("hash: {}\n", pod.hash), "");
+    assert_eq!(
+        Pod::from_spec_str(&undeclared, SpecFormat::Yaml)?.hash,
+        pod.hash,
+        "Hash should be computed when not declared."
+    );
+
+    let tampered = Pod::from_spec_str(&document.replace("tail -f", "head -f"), SpecFormat::Yaml)
+        .err()
+        .ok_or("Content not matching the declared hash should be rejected.")?;
+    let OrcaError::SpecHashMismatch(mismatch) = tampered else {
+        return Err(format!("Unexpected error: {tampered}").into());
+    };
+    assert_eq!(mismatch.declared, pod.hash);
+
+    let wrong_class = PodJob::from_spec_str(&document, SpecFormat::Yaml)
+        .err()
+        .ok_or("Pod document should not parse as a pod job.")?;
+    assert!(
+        wrong_class.kind() == ErrorKind::InvalidDocument,
+        "Unexpected error: {wrong_class}"
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_hand_written_document() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let document = formatdoc! {"
+        class: pod
+        annotation:
+          name: style-transfer
+          description: This is an example pod.
+          version: 0.67.0
+        hash: {hash}
+        source_commit_url: https://github.com/zenml-io/zenml/tree/0.67.0
+        image: zenmldocker/zenml-server:0.67.0
+        command: tail -f /dev/null
+        input_stream_map:
+          painting: {{path: /input/painting.png/, match_pattern: /input/painting.png}}
+          image: {{path: /input//image.png, match_pattern: /input/image.png}}
+        output_dir: /output/
+        output_stream_map:
+          styled: {{path: ./styled.png, match_pattern: ./styled.png}}
+        recommended_cpus: 0.25
+        recommended_memory: 2147483648
+        required_gpu: null
+    ", hash = pod.hash};
+    assert_eq!(
+        Pod::from_spec_str(&document, SpecFormat::Yaml)?.hash,
+        pod.hash,
+        "Paths in a document should be normalized like in `Pod::new`."
+    );
+
+    let cyclic = Pipeline::from_spec_str(
+        &formatdoc! {"
+            class: pipeline
+            annotation:
+              name: feedback
+              description: Restyles its own output.
+              version: 0.1.0
+            nodes:
+              first: !Hash {hash}
+              second: !Hash {hash}
+            edges:
+            - {{source: {{node: first, key: styled}}, target: {{node: second, key: image}}}}
+            - {{source: {{node: second, key: styled}}, target: {{node: first, key: image}}}}
+            input_map: {{}}
+        ", hash = pod.hash},
+        SpecFormat::Yaml,
+    )
+    .err()
+    .ok_or("Cyclic pipeline document should be rejected.")?;
+    assert!(
+        cyclic.kind() == ErrorKind::CyclicPipeline,
+        "Unexpected error: {cyclic}"
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_document_checked_against_references() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let store = InMemoryStore::new();
+    store.save(&pod)?;
+    let pod_job = pod_job_style(&pod)?;
+    let document = serde_yaml::from_str::<Value>(
+        &to_document(&pod_job, SpecFormat::Yaml)?
+            .replace(&format!("hash: {}\n", pod_job.hash), "")
+            .replace("  painting: !File /data/starry-night.png\n", ""),
+    )?;
+    assert!(
+        PodJob::from_document(document.clone()).is_ok(),
+        "Checks against the pod should be skipped without a resolver."
+    );
+    let invalid = PodJob::from_document_with(document, &store)
+        .err()
+        .ok_or("Job missing an input should be rejected.")?;
+    let OrcaError::InvalidInputPacket(packet) = invalid else {
+        return Err(format!("Unexpected error: {invalid}").into());
+    };
+    assert_eq!(packet.missing, vec!["painting".to_owned()]);
+    Ok(())
+}
+
+/// Hash of `instance` after a round trip through a document in `format`.
+fn document_round_trip<T: Model>(instance: &T, format: SpecFormat) -> Result<String, OrcaError> {
+    Ok(T::from_spec_str(&to_document(instance, format)?, format)?
+        .hash()
+        .to_owned())
+}
+
+#[test]
+fn verify_spec_formats() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let pod_job = pod_job_style(&pod)?;
+    let pod_result = pod_result_style(&pod_job, &pod)?;
+    let pipeline = pipeline_style(pipeline_style_edges())?;
+    for format in [SpecFormat::Yaml, SpecFormat::Json, SpecFormat::Toml] {
+        assert_eq!(
+            (
+                document_round_trip(&pod, format)?,
+                document_round_trip(&pod_job, format)?,
+                document_round_trip(&pod_result, format)?,
+                document_round_trip(&pipeline, format)?,
+            ),
+            (
+                pod.hash.clone(),
+                pod_job.hash.clone(),
+                pod_result.hash.clone(),
+                pipeline.hash.clone(),
+            ),
+            "Hashes should be the same in {format:?}."
+        );
+    }
+
+    assert_eq!(
+        to_spec(&pod, SpecFormat::Json)?
+            .lines()
+            .take(3)
+            .collect::<Vec<_>>(),
+        vec!["{", r#"  "class": "pod","#, r#"  "schema_version": 1,"#],
+        "JSON spec should lead with the header."
+    );
+    let toml = to_spec(&pod, SpecFormat::Toml)?;
+    assert!(
+        toml.starts_with("class = \"pod\"\nschema_version = 1\n"),
+        "TOML spec should lead with the header."
+    );
+    assert!(
+        !toml.contains("required_gpu"),
+        "TOML has no null so unset fields should be left out."
     );
     Ok(())
 }
@@ -21,6 +407,7 @@ fn verify_pod_to_yaml() -> Result<(), Box<dyn Error>> {
         to_yaml::<Pod>(&pod_style()?)?,
         indoc! {"
             class: pod
+            schema_version: 1
             command: tail -f /dev/null
             image: zenmldocker/zenml-server:0.67.0
             input_stream_map:
@@ -43,3 +430,270 @@ fn verify_pod_to_yaml() -> Result<(), Box<dyn Error>> {
     );
     Ok(())
 }
+
+#[test]
+fn verify_pod_job_to_yaml() -> Result<(), Box<dyn Error>> {
+    assert_eq!(
+        to_yaml::<PodJob>(&pod_job_style(&pod_style()?)?)?,
+        indoc! {"
+            class: podjob
+            schema_version: 1
+            input_packet:
+              image: !Blob B3F1D7A2E2C04B6B8B6A7D1C9E5F4A3B2C1D0E9F8A7B6C5D4E3F2A1B0C9D8E7F
+              painting: !File /data/starry-night.png
+            output_dir: /data/output
+            pod_hash: sha256:FEE2392F679AB826697964930B070373656E9DE5118F0DCC88B1F1A595B90F50
+        "}
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_pod_job_rejects_mismatched_input_packet() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let result = PodJob::new(
+        Annotation {
+            name: "style-transfer-job".to_owned(),
+            description: "Missing the painting input.".to_owned(),
+            version: Version::new(0, 1, 0),
+        },
+        &pod,
+        BTreeMap::from([
+            (
+                "image".to_owned(),
+                Input::File(PathBuf::from("/data/image.png")),
+            ),
+            (
+                "extra".to_owned(),
+                Input::File(PathBuf::from("/data/extra.png")),
+            ),
+        ]),
+        PathBuf::from("/data/output"),
+    );
+    let error = result.err().ok_or("Job should fail validation.")?;
+    let OrcaError::InvalidInputPacket(invalid) = error else {
+        return Err("Unexpected error type.".into());
+    };
+    assert_eq!(invalid.missing, vec!["painting".to_owned()]);
+    assert_eq!(invalid.unexpected, vec!["extra".to_owned()]);
+    Ok(())
+}
+
+#[test]
+fn verify_pod_result_to_yaml() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    assert_eq!(
+        to_yaml::<PodResult>(&pod_result_style(&pod_job_style(&pod)?, &pod)?)?,
+        indoc! {"
+            class: podresult
+            schema_version: 1
+            created: 1727000000
+            exit_code: 0
+            output_packet:
+              styled:
+                path: /data/output/styled.png
+                hash: 6D1B0C9E8F7A6B5C4D3E2F1A0B9C8D7E6F5A4B3C2D1E0F9A8B7C6D5E4F3A2B1C
+            pod_job_hash: sha256:6EFD1876B81FD96541AD0ADD05872FBEAC4EF0F2D3B2BE922B1E5C12BEE14481
+            status: Succeeded
+            terminated: 1727000042
+        "}
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_pod_result_requires_outputs_on_success() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let pod_job = pod_job_style(&pod)?;
+    let annotation = || Annotation {
+        name: "style-transfer-result".to_owned(),
+        description: "No outputs produced.".to_owned(),
+        version: Version::new(0, 1, 0),
+    };
+
+    let error = PodResult::new(
+        annotation(),
+        &pod_job,
+        &pod,
+        Status::Succeeded,
+        0,
+        1_727_000_000,
+        1_727_000_042,
+        BTreeMap::new(),
+    )
+    .err()
+    .ok_or("Succeeded result should require every output.")?;
+    let OrcaError::InvalidOutputPacket(invalid) = error else {
+        return Err("Unexpected error type.".into());
+    };
+    assert_eq!(invalid.missing, vec!["styled".to_owned()]);
+
+    PodResult::new(
+        annotation(),
+        &pod_job,
+        &pod,
+        Status::Failed,
+        1,
+        1_727_000_000,
+        1_727_000_042,
+        BTreeMap::new(),
+    )?;
+    Ok(())
+}
+
+#[test]
+fn verify_pod_result_rejects_termination_before_creation() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let pod_job = pod_job_style(&pod)?;
+    let error = PodResult::new(
+        Annotation {
+            name: "style-transfer-result".to_owned(),
+            description: "Ends before it starts.".to_owned(),
+            version: Version::new(0, 1, 0),
+        },
+        &pod_job,
+        &pod,
+        Status::Failed,
+        1,
+        1_727_000_042,
+        1_727_000_000,
+        BTreeMap::new(),
+    )
+    .err()
+    .ok_or("Result terminating before its creation should be rejected.")?;
+    let OrcaError::TerminatedBeforeCreated(invalid) = error else {
+        return Err(format!("Unexpected error: {error}").into());
+    };
+    assert_eq!(
+        (invalid.created, invalid.terminated),
+        (1_727_000_042, 1_727_000_000)
+    );
+
+    let pod_result = pod_result_style(&pod_job, &pod)?;
+    let document = to_document(&pod_result, SpecFormat::Yaml)?
+        .replace(&format!("hash: {}\n", pod_result.hash), "")
+        .replace("terminated: 1727000042", "terminated: 1726999999");
+    let parsed = PodResult::from_spec_str(&document, SpecFormat::Yaml)
+        .err()
+        .ok_or("Document terminating before its creation should be rejected.")?;
+    assert!(
+        parsed.kind() == ErrorKind::TerminatedBeforeCreated,
+        "Unexpected error: {parsed}"
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_pipeline_to_yaml() -> Result<(), Box<dyn Error>> {
+    assert_eq!(
+        to_yaml::<Pipeline>(&pipeline_style(pipeline_style_edges())?)?,
+        indoc! {"
+            class: pipeline
+            schema_version: 1
+            edges:
+            - source:
+                node: first
+                key: styled
+              target:
+                node: second
+                key: image
+            input_map:
+              image:
+              - node: first
+                key: image
+              painting:
+              - node: first
+                key: painting
+              - node: second
+                key: painting
+            nodes:
+              first: !Annotation
+                name: style-transfer
+                version: 0.67.0
+              second: !Hash sha256:FEE2392F679AB826697964930B070373656E9DE5118F0DCC88B1F1A595B90F50
+        "}
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_pipeline_rejects_invalid_graphs() -> Result<(), Box<dyn Error>> {
+    let error = pipeline_style(vec![])
+        .err()
+        .ok_or("Pipeline without edges should leave an input unsatisfied.")?;
+    let OrcaError::UnsatisfiedInput(unsatisfied) = error else {
+        return Err("Unexpected error type.".into());
+    };
+    assert_eq!(unsatisfied.port, port("second", "image"));
+
+    let cyclic_error = Pipeline::new(
+        Annotation {
+            name: "style-transfer-loop".to_owned(),
+            description: "Each node restyles the other's output.".to_owned(),
+            version: Version::new(0, 1, 0),
+        },
+        BTreeMap::from([
+            ("first".to_owned(), PodRef::Hash(pod_style()?.hash)),
+            ("second".to_owned(), PodRef::Hash(pod_style()?.hash)),
+        ]),
+        vec![
+            Edge {
+                source: port("second", "styled"),
+                target: port("first", "image"),
+            },
+            Edge {
+                source: port("first", "styled"),
+                target: port("second", "image"),
+            },
+        ],
+        BTreeMap::from([(
+            "painting".to_owned(),
+            vec![port("first", "painting"), port("second", "painting")],
+        )]),
+        |_| pod_style(),
+    )
+    .err()
+    .ok_or("Pipeline with a loop should be rejected.")?;
+    let OrcaError::CyclicPipeline(cyclic) = cyclic_error else {
+        return Err("Unexpected error type.".into());
+    };
+    assert_eq!(cyclic.nodes, vec!["first".to_owned(), "second".to_owned()]);
+    Ok(())
+}
+
+#[test]
+fn verify_model_class() {
+    assert_eq!(Pod::class(), "pod");
+    assert_eq!(PodJob::class(), "podjob");
+    assert_eq!(PodResult::class(), "podresult");
+    assert_eq!(Pipeline::class(), "pipeline");
+}
+
+#[test]
+fn verify_annotation_requires_semver() {
+    assert!(serde_yaml::from_str::<Annotation>(indoc! {"
+        name: style-transfer
+        version: '1.0'
+        description: Missing the patch version.
+    "})
+    .is_err());
+}
+
+#[test]
+fn verify_annotation_name_rules() -> Result<(), Box<dyn Error>> {
+    assert!(Annotation::validate_name("style-transfer-2").is_ok());
+    let empty = Annotation::validate_name("")
+        .err()
+        .ok_or("Empty name should be rejected.")?;
+    assert_eq!(empty.rule, "must not be empty");
+
+    let error = pod_custom("style transfer", "0.1.0", "tail -f /dev/null")
+        .err()
+        .ok_or("Pod with a space in its name should be rejected.")?;
+    let OrcaError::InvalidAnnotationName(invalid) = error else {
+        return Err("Unexpected error type.".into());
+    };
+    assert_eq!(invalid.name, "style transfer");
+    assert!(invalid.rule.ends_with("found ` `"));
+    Ok(())
+}
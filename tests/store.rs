@@ -1,13 +1,45 @@
 #![expect(clippy::panic_in_result_fn, reason = "Panics OK in tests.")]
 
 pub mod fixture;
-use fixture::{add_pod_storage, pod_style, store_test};
+use fixture::{
+    add_pod_storage, greeter, pipeline_style, pipeline_style_edges, pod_custom, pod_job_style,
+    pod_result_style, pod_style, store_test, Greeter,
+};
+use glob::Pattern;
+#[cfg(feature = "sqlite")]
+use orcapod::store::sqlitestore::SqliteStore;
 use orcapod::{
-    error::FileHasNoParent,
-    model::{to_yaml, Pod},
+    error::{ErrorKind, FileHasNoParent, OrcaError},
+    model::{
+        to_yaml, Annotation, HashAlgorithm, Model, Multihash, Pipeline, Pod, PodJob, PodRef,
+        PodResult, Reference, SpecFormat, Version, VersionReq,
+    },
+    store::{
+        filestore::{GcOptions, LocalFileStore},
+        memstore::InMemoryStore,
+        DeleteMode, HashMismatch, ListFilter, Store,
+    },
+};
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    env,
+    error::Error,
+    fs::{self, File},
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, SystemTime},
 };
-use std::{error::Error, fs, path::Path};
 use tempfile::tempdir;
+use tracing_test::traced_test;
+
+fn list_hashes<T: Model>(store: &impl Store) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(store
+        .list::<T>(&ListFilter::default())?
+        .into_iter()
+        .map(|record| record.hash)
+        .collect())
+}
 
 fn is_dir_two_levels_up_empty(file: &Path) -> Result<bool, Box<dyn Error>> {
     Ok(file
@@ -34,7 +66,7 @@ fn verify_pod_save_and_delete() -> Result<(), Box<dyn Error>> {
             "pod",
             &pod_style.hash,
             &pod_style.annotation.name,
-            &pod_style.annotation.version,
+            &pod_style.annotation.version.to_string(),
         );
         let spec_file = store.make_spec_path("pod", &pod_style.hash);
         {
@@ -51,3 +83,1239 @@ fn verify_pod_save_and_delete() -> Result<(), Box<dyn Error>> {
     assert!(!fs::exists(&store_directory)?);
     Ok(())
 }
+
+#[test]
+fn verify_pod_job_save_load_and_delete() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let pod_job = pod_job_style(&pod_style()?)?;
+    store.save(&pod_job)?;
+
+    let loaded = store.load::<PodJob>(
+        &pod_job.annotation.name,
+        &pod_job.annotation.version.to_string(),
+    )?;
+    assert_eq!(loaded.hash, pod_job.hash);
+    assert_eq!(to_yaml::<PodJob>(&loaded)?, to_yaml::<PodJob>(&pod_job)?);
+    assert_eq!(list_hashes::<PodJob>(&*store)?, vec![pod_job.hash.clone()],);
+
+    store.delete::<PodJob>(
+        &pod_job.annotation.name,
+        &pod_job.annotation.version.to_string(),
+        DeleteMode::Restrict,
+    )?;
+    assert!(list_hashes::<PodJob>(&*store)?.is_empty());
+    assert!(!store
+        .make_spec_path(&PodJob::class(), &pod_job.hash)
+        .exists());
+    Ok(())
+}
+
+#[test]
+fn verify_pod_result_save_load_and_delete() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let pod = pod_style()?;
+    let pod_result = pod_result_style(&pod_job_style(&pod)?, &pod)?;
+    store.save(&pod_result)?;
+    assert!(store
+        .make_spec_path(&PodResult::class(), &pod_result.hash)
+        .exists());
+
+    let loaded = store.load::<PodResult>(
+        &pod_result.annotation.name,
+        &pod_result.annotation.version.to_string(),
+    )?;
+    assert_eq!(
+        to_yaml::<PodResult>(&loaded)?,
+        to_yaml::<PodResult>(&pod_result)?
+    );
+
+    store.delete::<PodResult>(
+        &pod_result.annotation.name,
+        &pod_result.annotation.version.to_string(),
+        DeleteMode::Restrict,
+    )?;
+    assert!(list_hashes::<PodResult>(&*store)?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn verify_pipeline_save_load_and_delete() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let pipeline = pipeline_style(pipeline_style_edges())?;
+    store.save(&pipeline)?;
+
+    let loaded = store.load::<Pipeline>(
+        &pipeline.annotation.name,
+        &pipeline.annotation.version.to_string(),
+    )?;
+    assert_eq!(
+        to_yaml::<Pipeline>(&loaded)?,
+        to_yaml::<Pipeline>(&pipeline)?
+    );
+
+    store.delete::<Pipeline>(
+        &pipeline.annotation.name,
+        &pipeline.annotation.version.to_string(),
+        DeleteMode::Restrict,
+    )?;
+    assert!(!store
+        .make_spec_path(&Pipeline::class(), &pipeline.hash)
+        .exists());
+    Ok(())
+}
+
+fn verify_shared_spec_semantics(store: &impl Store) -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let mut alias = pod_style()?;
+    "style-transfer-alias".clone_into(&mut alias.annotation.name);
+
+    store.save(&pod)?;
+    let duplicate = store
+        .save(&pod)
+        .err()
+        .ok_or("Saving a duplicate annotation should fail.")?;
+    assert_eq!(
+        duplicate.kind(),
+        ErrorKind::AnnotationExists,
+        "Every store should reject a duplicate annotation the same way."
+    );
+    store.save(&alias)?;
+    assert_eq!(
+        list_hashes::<Pod>(store)?,
+        vec![pod.hash.clone(); 2],
+        "Both annotations should point at the same spec."
+    );
+
+    // the spec is kept while any annotation still references it
+    store.delete::<Pod>(
+        &pod.annotation.name,
+        &pod.annotation.version.to_string(),
+        DeleteMode::Restrict,
+    )?;
+    assert_eq!(
+        store
+            .load::<Pod>(
+                &alias.annotation.name,
+                &alias.annotation.version.to_string()
+            )?
+            .hash,
+        pod.hash,
+        "Spec should survive while the alias references it."
+    );
+    store.delete::<Pod>(
+        &alias.annotation.name,
+        &alias.annotation.version.to_string(),
+        DeleteMode::Restrict,
+    )?;
+    assert!(
+        list_hashes::<Pod>(store)?.is_empty(),
+        "No annotations should remain."
+    );
+    assert!(
+        store
+            .load::<Pod>(&pod.annotation.name, &pod.annotation.version.to_string())
+            .is_err(),
+        "Deleted annotation should no longer load."
+    );
+    Ok(())
+}
+
+fn verify_load_by_hash_semantics(store: &impl Store) -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let mut alias = pod_style()?;
+    "style-transfer-alias".clone_into(&mut alias.annotation.name);
+    let other = pod_custom("style-transfer", "0.68.0", "sleep infinity")?;
+    store.save(&pod)?;
+    store.save(&alias)?;
+    store.save(&other)?;
+
+    let digest = pod.hash.trim_start_matches("sha256:");
+    let (loaded, annotations) =
+        store.load_by_hash::<Pod>(digest.get(..8).ok_or("Hash too short.")?)?;
+    assert_eq!(
+        loaded.hash, pod.hash,
+        "Short digest prefix should resolve the spec."
+    );
+    assert_eq!(
+        store
+            .load_by_hash::<Pod>(pod.hash.get(..15).ok_or("Hash too short.")?)?
+            .0
+            .hash,
+        pod.hash,
+        "Short prefix with the algorithm should resolve the spec."
+    );
+    assert_eq!(
+        annotations
+            .iter()
+            .map(|annotation| annotation.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["style-transfer", "style-transfer-alias"],
+        "Every annotation on the spec should be returned."
+    );
+    assert_eq!(
+        loaded.annotation.name, "style-transfer",
+        "Model should carry the first annotation."
+    );
+
+    let ambiguous = store
+        .load_by_hash::<Pod>("")
+        .err()
+        .ok_or("Expected error.")?;
+    assert!(
+        ambiguous.kind() == ErrorKind::AmbiguousHash,
+        "Empty prefix should match both specs."
+    );
+    let missing = store
+        .load_by_hash::<Pod>("XYZ")
+        .err()
+        .ok_or("Expected error.")?;
+    assert!(
+        missing.kind() == ErrorKind::NoSpecFound,
+        "Unknown prefix should match nothing."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_shared_spec() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    verify_shared_spec_semantics(&*store)?;
+    assert!(!store.make_spec_path("pod", &pod_style()?.hash).exists());
+    Ok(())
+}
+
+#[test]
+fn verify_in_memory_store_shared_spec() -> Result<(), Box<dyn Error>> {
+    verify_shared_spec_semantics(&InMemoryStore::new())
+}
+
+#[test]
+fn verify_in_memory_store_round_trip() -> Result<(), Box<dyn Error>> {
+    let store = InMemoryStore::new();
+    let pod = pod_style()?;
+    let pipeline = pipeline_style(pipeline_style_edges())?;
+    store.save(&pod)?;
+    store.save(&pipeline)?;
+
+    assert_eq!(
+        to_yaml::<Pod>(
+            &store.load::<Pod>(&pod.annotation.name, &pod.annotation.version.to_string())?
+        )?,
+        to_yaml::<Pod>(&pod)?
+    );
+    assert_eq!(list_hashes::<Pipeline>(&store)?, vec![pipeline.hash]);
+    assert_eq!(
+        store
+            .list::<Pod>(&ListFilter::default())?
+            .into_iter()
+            .map(|record| record.name)
+            .collect::<Vec<_>>(),
+        vec![pod.annotation.name]
+    );
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn verify_sqlite_store_shared_spec() -> Result<(), Box<dyn Error>> {
+    verify_shared_spec_semantics(&SqliteStore::new(":memory:")?)
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn verify_sqlite_store_persists_same_hash() -> Result<(), Box<dyn Error>> {
+    let directory = tempdir()?;
+    let database = directory.path().join("store.db");
+    let pod = pod_style()?;
+    SqliteStore::new(&database)?.save(&pod)?;
+
+    let loaded = SqliteStore::new(&database)?
+        .load::<Pod>(&pod.annotation.name, &pod.annotation.version.to_string())?;
+    assert_eq!(loaded.hash, pod.hash);
+    assert_eq!(to_yaml::<Pod>(&loaded)?, to_yaml::<Pod>(&pod)?);
+    Ok(())
+}
+
+#[test]
+fn verify_load_by_hash() -> Result<(), Box<dyn Error>> {
+    verify_load_by_hash_semantics(&*store_test(None)?)?;
+    verify_load_by_hash_semantics(&InMemoryStore::new())?;
+    #[cfg(feature = "sqlite")]
+    verify_load_by_hash_semantics(&SqliteStore::new(":memory:")?)?;
+    Ok(())
+}
+
+fn verify_list_filter_semantics(store: &impl Store) -> Result<(), Box<dyn Error>> {
+    let blur = pod_custom("blur", "1.0.0", "blur")?;
+    store.save(&pod_style()?)?;
+    store.save(&pod_custom("style-transfer", "0.9.0", "style --old")?)?;
+    store.save(&pod_custom("style-transfer", "0.10.0", "style --new")?)?;
+    store.save(&blur)?;
+
+    let versions = |filter: &ListFilter| -> Result<Vec<String>, Box<dyn Error>> {
+        let mut found = store
+            .list::<Pod>(filter)?
+            .into_iter()
+            .map(|record| format!("{}:{}", record.name, record.version))
+            .collect::<Vec<_>>();
+        found.sort();
+        Ok(found)
+    };
+    assert_eq!(
+        versions(&ListFilter {
+            name: Some(Pattern::new("style-*")?),
+            version: Some(VersionReq::parse(">=0.10.0, <0.67.0")?),
+            ..ListFilter::default()
+        })?,
+        vec!["style-transfer:0.10.0"],
+        "Versions should compare numerically, not lexically."
+    );
+    assert_eq!(
+        versions(&ListFilter {
+            name: Some(Pattern::new("[!s]*")?),
+            ..ListFilter::default()
+        })?,
+        vec!["blur:1.0.0"],
+        "Negated character classes should match the same in every backend."
+    );
+    assert_eq!(
+        versions(&ListFilter {
+            hash_prefix: Some(blur.hash.clone()),
+            ..ListFilter::default()
+        })?,
+        vec!["blur:1.0.0"],
+        "Hash prefix should select a single spec."
+    );
+    assert_eq!(
+        versions(&ListFilter {
+            hash_prefix: blur
+                .hash
+                .trim_start_matches("sha256:")
+                .get(..8)
+                .map(str::to_owned),
+            ..ListFilter::default()
+        })?,
+        vec!["blur:1.0.0"],
+        "Digest prefix should select the spec as well."
+    );
+
+    let records = store
+        .list_iter::<Pod>(&ListFilter::default())?
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(records.len(), 4, "Streaming should yield every record.");
+    assert!(
+        records
+            .iter()
+            .all(|record| record.description == "This is an example pod."),
+        "Records should carry the annotation description."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_list_filter() -> Result<(), Box<dyn Error>> {
+    verify_list_filter_semantics(&*store_test(None)?)?;
+    verify_list_filter_semantics(&InMemoryStore::new())?;
+    #[cfg(feature = "sqlite")]
+    verify_list_filter_semantics(&SqliteStore::new(":memory:")?)?;
+    Ok(())
+}
+
+fn verify_version_requirement_semantics(store: &impl Store) -> Result<(), Box<dyn Error>> {
+    for (version, command) in [
+        ("0.9.0", "style --old"),
+        ("0.10.0", "style --new"),
+        ("0.67.0", "tail -f /dev/null"),
+        ("1.0.0-alpha", "style --next"),
+    ] {
+        store.save(&pod_custom("style-transfer", version, command)?)?;
+    }
+
+    let resolve = |requirement: &str| -> Result<String, Box<dyn Error>> {
+        Ok(store
+            .load::<Pod>("style-transfer", requirement)?
+            .annotation
+            .version
+            .to_string())
+    };
+    assert_eq!(
+        resolve("latest")?,
+        "0.67.0",
+        "Latest should skip pre-releases."
+    );
+    assert_eq!(resolve("^0.9")?, "0.9.0", "Caret should stay on the minor.");
+    assert_eq!(
+        resolve("~0.10")?,
+        "0.10.0",
+        "Tilde should stay on the minor."
+    );
+    assert_eq!(
+        resolve(">=0.10")?,
+        "0.67.0",
+        "Range should pick the highest."
+    );
+    assert_eq!(
+        resolve("1.0.0-alpha")?,
+        "1.0.0-alpha",
+        "Exact should load as is."
+    );
+    assert!(
+        store
+            .load::<Pod>("style-transfer", "^2")
+            .err()
+            .ok_or("Expected error.")?
+            .kind()
+            == ErrorKind::NoAnnotationFound,
+        "Unmatched requirement should find no annotation."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_version_requirements() -> Result<(), Box<dyn Error>> {
+    verify_version_requirement_semantics(&*store_test(None)?)?;
+    verify_version_requirement_semantics(&InMemoryStore::new())?;
+    #[cfg(feature = "sqlite")]
+    verify_version_requirement_semantics(&SqliteStore::new(":memory:")?)?;
+    Ok(())
+}
+
+fn verify_invalid_name_semantics(store: &impl Store) -> Result<(), Box<dyn Error>> {
+    let mut pod = pod_style()?;
+    "style_transfer".clone_into(&mut pod.annotation.name);
+    let error = store.save(&pod).err().ok_or("Save should be rejected.")?;
+    let OrcaError::InvalidAnnotationName(invalid) = error else {
+        return Err("Unexpected error type.".into());
+    };
+    assert!(
+        invalid.rule.contains('_'),
+        "Rule should name the bad character."
+    );
+    assert!(
+        list_hashes::<Pod>(store)?.is_empty(),
+        "Nothing should be stored."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_invalid_name_rejected_on_save() -> Result<(), Box<dyn Error>> {
+    verify_invalid_name_semantics(&*store_test(None)?)?;
+    verify_invalid_name_semantics(&InMemoryStore::new())?;
+    #[cfg(feature = "sqlite")]
+    verify_invalid_name_semantics(&SqliteStore::new(":memory:")?)?;
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_fsck() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let pod = pod_style()?;
+    store.save(&pod)?;
+    let annotation_dir = store.directory.join("annotation/pod");
+    let bad_name = annotation_dir.join(format!("style_transfer/{}-0.1.0.yaml", pod.hash));
+    let bad_file = annotation_dir.join("style-transfer/notes.yaml");
+    for path in [&bad_name, &bad_file] {
+        fs::create_dir_all(path.parent().ok_or("No parent.")?)?;
+        fs::write(path, "")?;
+    }
+
+    assert_eq!(
+        list_hashes::<Pod>(&*store)?,
+        vec![pod.hash],
+        "Listing should skip non-conforming files."
+    );
+    let mut reported = store
+        .fsck()?
+        .into_iter()
+        .map(|found| found.path)
+        .collect::<Vec<_>>();
+    reported.sort();
+    assert_eq!(reported, vec![bad_file, bad_name]);
+    Ok(())
+}
+
+#[test]
+#[traced_test]
+fn verify_local_store_logs_skipped_spec() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let mut alias = pod_style()?;
+    "style-transfer-alias".clone_into(&mut alias.annotation.name);
+
+    store.save(&pod_style()?)?;
+    assert!(!logs_contain("Skip saving"), "First save writes the spec.");
+    store.save(&alias)?;
+    assert!(
+        logs_contain("Skip saving since it is already stored."),
+        "Second save should reuse the spec."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_recovers_interrupted_writes() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let pod = pod_style()?;
+    let dangling = pod_custom("style-transfer", "1.0.0", "sleep 10")?;
+    store.save(&pod)?;
+    store.save(&dangling)?;
+
+    // simulate crashes mid-write: a spec that never landed and a leftover temp file
+    fs::remove_dir_all(
+        store
+            .make_spec_path("pod", &dangling.hash)
+            .parent()
+            .ok_or("No parent.")?,
+    )?;
+    let temp_file = store.directory.join("pod/.spec.yaml.1-0.tmp");
+    fs::write(&temp_file, "class: pod\n")?;
+    File::options()
+        .write(true)
+        .open(&temp_file)?
+        .set_modified(SystemTime::now() - Duration::from_hours(1))?;
+    // another process may still be writing this one
+    let in_flight_file = store.directory.join("pod/.spec.yaml.2-0.tmp");
+    fs::write(&in_flight_file, "class: pod\n")?;
+
+    let recovered = LocalFileStore::open(&store.directory)?;
+    assert!(!temp_file.exists(), "Stale temp file should be removed.");
+    assert!(in_flight_file.exists(), "Recent temp file should be kept.");
+    assert_eq!(
+        list_hashes::<Pod>(&recovered)?,
+        vec![pod.hash.clone()],
+        "Dangling annotation should no longer be listed."
+    );
+    assert!(
+        store
+            .directory
+            .join("quarantine")
+            .join(
+                store
+                    .make_annotation_path("pod", &dangling.hash, "style-transfer", "1.0.0")
+                    .strip_prefix(&store.directory)?
+            )
+            .exists(),
+        "Dangling annotation should be quarantined."
+    );
+    assert_eq!(
+        recovered.load::<Pod>("style-transfer", "latest")?.hash,
+        pod.hash
+    );
+    Ok(())
+}
+
+const LOCK_WORKERS: usize = 4;
+
+/// Run as a child process by `verify_local_store_multi_process`, no-op otherwise.
+#[test]
+fn local_store_lock_worker() -> Result<(), Box<dyn Error>> {
+    let Ok(directory) = env::var("ORCAPOD_LOCK_WORKER_DIR") else {
+        return Ok(());
+    };
+    let store = LocalFileStore::new(directory);
+    // same spec under a different name per worker so they all contend on the hash
+    let mut pod = pod_style()?;
+    format!("worker-{}", env::var("ORCAPOD_LOCK_WORKER_INDEX")?)
+        .clone_into(&mut pod.annotation.name);
+    for _ in 0..25 {
+        store.save(&pod)?;
+        store.delete::<Pod>(&pod.annotation.name, "0.67.0", DeleteMode::Restrict)?;
+    }
+    store.save(&pod)?;
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_multi_process() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let workers = (0..LOCK_WORKERS)
+        .map(|index| {
+            Command::new(env::current_exe()?)
+                .args(["--exact", "local_store_lock_worker", "--test-threads", "1"])
+                .env("ORCAPOD_LOCK_WORKER_DIR", &store.directory)
+                .env("ORCAPOD_LOCK_WORKER_INDEX", index.to_string())
+                .stdout(Stdio::null())
+                .spawn()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    for mut worker in workers {
+        assert!(worker.wait()?.success(), "Worker should finish cleanly.");
+    }
+
+    for index in 0..LOCK_WORKERS {
+        assert_eq!(
+            store
+                .load::<Pod>(&format!("worker-{index}"), "0.67.0")?
+                .hash,
+            pod_style()?.hash,
+            "Every annotation should still have its spec."
+        );
+    }
+    Ok(())
+}
+
+fn verify_clean_store_semantics(store: &impl Store) -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    store.save(&pod)?;
+    store.save(&pod_job_style(&pod)?)?;
+    assert!(store.verify::<Pod>()?.is_clean(), "Pods should verify.");
+    assert!(store.verify::<PodJob>()?.is_clean(), "Jobs should verify.");
+    Ok(())
+}
+
+#[test]
+fn verify_clean_store() -> Result<(), Box<dyn Error>> {
+    verify_clean_store_semantics(&*store_test(None)?)?;
+    verify_clean_store_semantics(&InMemoryStore::new())?;
+    #[cfg(feature = "sqlite")]
+    verify_clean_store_semantics(&SqliteStore::new(":memory:")?)?;
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_verify_report() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let dangling = pod_custom("style-transfer", "1.0.0", "sleep 10")?;
+    let tampered = pod_custom("style-transfer", "2.0.0", "sleep 20")?;
+    store.save(&pod_style()?)?;
+    store.save(&dangling)?;
+    store.save(&tampered)?;
+
+    fs::remove_dir_all(
+        store
+            .make_spec_path("pod", &dangling.hash)
+            .parent()
+            .ok_or("No parent.")?,
+    )?;
+    let tampered_spec = store.make_spec_path("pod", &tampered.hash);
+    fs::write(
+        &tampered_spec,
+        fs::read_to_string(&tampered_spec)?.replace("sleep 20", "sleep 30"),
+    )?;
+    let orphan_spec = store.make_spec_path("pod", "ABCDEF");
+    fs::create_dir_all(orphan_spec.parent().ok_or("No parent.")?)?;
+    fs::write(&orphan_spec, "class: pod\nimage: [unclosed\n")?;
+
+    let report = store.verify::<Pod>()?;
+    assert_eq!(
+        report.hash_mismatches,
+        vec![HashMismatch {
+            expected: tampered.hash,
+            actual: pod_custom("style-transfer", "2.0.0", "sleep 30")?.hash,
+        }]
+    );
+    assert_eq!(
+        report
+            .dangling_annotations
+            .into_iter()
+            .map(|record| (record.version.to_string(), record.hash))
+            .collect::<Vec<_>>(),
+        vec![("1.0.0".to_owned(), dangling.hash)]
+    );
+    assert_eq!(report.orphaned_specs, vec!["ABCDEF".to_owned()]);
+    assert_eq!(
+        report
+            .unparsable
+            .into_iter()
+            .map(|found| found.location)
+            .collect::<Vec<_>>(),
+        vec![orphan_spec.to_string_lossy().to_string()]
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_gc() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let referenced = pod_style()?;
+    let unreachable = pod_custom("style-transfer", "1.0.0", "sleep 10")?;
+    store.save(&referenced)?;
+    store.save(&pod_job_style(&referenced)?)?;
+    store.save(&unreachable)?;
+    // drop the pod annotations behind the store's back, only the job still points at a pod
+    for pod in [&referenced, &unreachable] {
+        fs::remove_file(store.make_annotation_path(
+            "pod",
+            &pod.hash,
+            "style-transfer",
+            &pod.annotation.version.to_string(),
+        ))?;
+    }
+    let unreachable_dir = store
+        .make_spec_path("pod", &unreachable.hash)
+        .parent()
+        .ok_or("No parent.")?
+        .to_path_buf();
+
+    let recent = store.gc(&GcOptions {
+        dry_run: false,
+        ..GcOptions::default()
+    })?;
+    assert_eq!(recent.within_grace_period, vec![unreachable_dir.clone()]);
+    assert!(recent.removed.is_empty(), "Recent specs should be kept.");
+
+    let dry_run = store.gc(&GcOptions {
+        dry_run: true,
+        grace_period: Duration::ZERO,
+    })?;
+    assert_eq!(dry_run.unreachable, vec![unreachable_dir.clone()]);
+    assert!(dry_run.removed.is_empty(), "Dry run shouldn't remove.");
+    assert!(unreachable_dir.exists(), "Dry run shouldn't remove.");
+
+    let swept = store.gc(&GcOptions {
+        dry_run: false,
+        grace_period: Duration::ZERO,
+    })?;
+    assert_eq!(swept.removed, vec![unreachable_dir.clone()]);
+    assert!(
+        !unreachable_dir.exists(),
+        "Unreachable spec should be removed."
+    );
+    assert!(
+        store.make_spec_path("pod", &referenced.hash).exists(),
+        "Spec referenced by a job should be kept."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_gc_refuses_unknown_classes() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let unreachable = pod_style()?;
+    store.save(&unreachable)?;
+    store.save(&greeter("hello")?)?;
+    fs::remove_file(store.make_annotation_path(
+        "pod",
+        &unreachable.hash,
+        "style-transfer",
+        &unreachable.annotation.version.to_string(),
+    ))?;
+
+    let error = store
+        .gc(&GcOptions {
+            dry_run: false,
+            grace_period: Duration::ZERO,
+        })
+        .err()
+        .ok_or("Garbage collection should refuse classes it can't follow references of.")?;
+    let OrcaError::UnknownModelClass(unknown) = error else {
+        return Err(format!("Unexpected error: {error}").into());
+    };
+    assert_eq!(unknown.classes, vec!["greeter".to_owned()]);
+    assert!(
+        store.make_spec_path("pod", &unreachable.hash).exists(),
+        "Nothing should be swept."
+    );
+    Ok(())
+}
+
+/// Lock files left under the store directory.
+fn lock_files(store: &LocalFileStore) -> Result<Vec<String>, Box<dyn Error>> {
+    glob::glob(&format!(
+        "{}/.lock/*/*/*.lock",
+        Pattern::escape(&store.directory.to_string_lossy())
+    ))?
+    .map(|path| {
+        Ok(path?
+            .strip_prefix(&store.directory)?
+            .to_string_lossy()
+            .to_string())
+    })
+    .collect()
+}
+
+#[test]
+fn verify_local_store_paths_are_portable() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let pod = pod_style()?;
+    store.save(&pod)?;
+    let stored = glob::glob(&format!(
+        "{}/**/*",
+        Pattern::escape(&store.directory.to_string_lossy())
+    ))?
+    .map(|path| Ok(path?.strip_prefix(&store.directory)?.to_path_buf()))
+    .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    assert!(
+        stored
+            .iter()
+            .all(|path| !path.to_string_lossy().contains(':')),
+        "Paths should be valid on every platform, found {stored:?}."
+    );
+    assert_eq!(
+        list_hashes::<Pod>(&*store)?,
+        vec![pod.hash],
+        "Hashes should read back with their prefix."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_removes_locks() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let pod = pod_style()?;
+    store.save(&pod)?;
+    assert_eq!(
+        lock_files(&store)?.len(),
+        2,
+        "Save should lock name and hash."
+    );
+    store.delete::<Pod>("style-transfer", "0.67.0", DeleteMode::Restrict)?;
+    assert!(
+        lock_files(&store)?.is_empty(),
+        "Delete should remove the locks of what it removed."
+    );
+
+    // left behind by a crash, or by a store from before locks were cleaned up
+    store.save(&pod)?;
+    let stale_lock = store.directory.join(".lock/hash/pod/gone.lock");
+    fs::write(&stale_lock, "")?;
+    let collected = store.gc(&GcOptions {
+        dry_run: false,
+        ..GcOptions::default()
+    })?;
+    assert_eq!(
+        collected.removed_locks,
+        vec![stale_lock],
+        "Only the lock of the missing spec should be removed."
+    );
+    assert_eq!(lock_files(&store)?.len(), 2, "Locks in use should be kept.");
+    Ok(())
+}
+
+fn verify_delete_integrity_semantics(store: &impl Store) -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let pod_job = pod_job_style(&pod)?;
+    let pipeline = pipeline_style(pipeline_style_edges())?;
+    store.save(&pod)?;
+    store.save(&pod_job)?;
+    store.save(&pod_result_style(&pod_job, &pod)?)?;
+    store.save(&pipeline)?;
+
+    let referrers = |error: OrcaError| {
+        if let OrcaError::StillReferenced(referenced) = error {
+            Ok(referenced.referrers)
+        } else {
+            Err("Unexpected error type.")
+        }
+    };
+    let restricted = store
+        .delete::<Pod>("style-transfer", "0.67.0", DeleteMode::Restrict)
+        .err()
+        .ok_or("Referenced pod shouldn't be deleted.")?;
+    assert_eq!(
+        referrers(restricted)?,
+        vec![
+            format!("podjob style-transfer-job:{}", pod_job.annotation.version),
+            format!(
+                "pipeline double-style-transfer:{}",
+                pipeline.annotation.version
+            ),
+        ],
+        "Job and pipeline should block the delete."
+    );
+
+    // another version keeps the spec, but the pipeline names this exact version
+    store.save(&pod_custom(
+        "style-transfer",
+        "0.68.0",
+        "tail -f /dev/null",
+    )?)?;
+    let named = store
+        .delete::<Pod>("style-transfer", "0.67.0", DeleteMode::Restrict)
+        .err()
+        .ok_or("Pod named by a pipeline shouldn't be deleted.")?;
+    assert_eq!(
+        referrers(named)?,
+        vec![format!(
+            "pipeline double-style-transfer:{}",
+            pipeline.annotation.version
+        )],
+        "Only the pipeline should block the delete."
+    );
+
+    store.delete::<Pod>("style-transfer", "0.67.0", DeleteMode::Cascade)?;
+    assert!(
+        list_hashes::<Pipeline>(store)?.is_empty(),
+        "Pipeline should be cascaded."
+    );
+    assert_eq!(
+        list_hashes::<PodJob>(store)?,
+        vec![pod_job.hash],
+        "Job should survive since its pod spec is still stored."
+    );
+
+    store.delete::<Pod>("style-transfer", "0.68.0", DeleteMode::Cascade)?;
+    assert!(
+        list_hashes::<PodJob>(store)?.is_empty() && list_hashes::<PodResult>(store)?.is_empty(),
+        "Job and its result should be cascaded."
+    );
+    assert!(
+        list_hashes::<Pod>(store)?.is_empty(),
+        "Pod should be deleted."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_delete_integrity() -> Result<(), Box<dyn Error>> {
+    verify_delete_integrity_semantics(&*store_test(None)?)?;
+    verify_delete_integrity_semantics(&InMemoryStore::new())?;
+    #[cfg(feature = "sqlite")]
+    verify_delete_integrity_semantics(&SqliteStore::new(":memory:")?)?;
+    Ok(())
+}
+
+fn verify_annotation_edit_semantics(store: &impl Store) -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    store.save(&pod)?;
+
+    store.update_description::<Pod>("style-transfer", "latest", "Fixed a typo.")?;
+    let updated = store.load::<Pod>("style-transfer", "0.67.0")?;
+    assert_eq!(
+        (
+            updated.annotation.description.as_str(),
+            updated.hash.as_str()
+        ),
+        ("Fixed a typo.", pod.hash.as_str()),
+        "Description should change while the hash stays."
+    );
+
+    let alias = Annotation {
+        name: "neural-style".to_owned(),
+        version: Version::new(1, 0, 0),
+        description: "Same pod, friendlier name.".to_owned(),
+    };
+    store.alias::<Pod>("style-transfer", "0.67.0", &alias)?;
+    assert_eq!(
+        store.load::<Pod>("neural-style", "1.0.0")?.hash,
+        pod.hash,
+        "Alias should point at the same spec."
+    );
+    assert_eq!(
+        store.load_by_hash::<Pod>(&pod.hash)?.1.len(),
+        2,
+        "Spec should have both annotations."
+    );
+    assert!(
+        store
+            .alias::<Pod>("style-transfer", "0.67.0", &alias)
+            .is_err(),
+        "Alias shouldn't replace a taken annotation."
+    );
+
+    // a pipeline names `style-transfer:0.67.0`, so it can't be renamed away
+    store.save(&pipeline_style(pipeline_style_edges())?)?;
+    let renamed = Annotation {
+        name: "style-transfer-v2".to_owned(),
+        version: Version::new(0, 67, 0),
+        description: String::new(),
+    };
+    assert_eq!(
+        store
+            .rename::<Pod>("style-transfer", "0.67.0", &renamed)
+            .err()
+            .map(|error| error.kind()),
+        Some(ErrorKind::StillReferenced),
+        "Rename should be refused while referenced."
+    );
+    assert!(
+        store.load::<Pod>("style-transfer-v2", "0.67.0").is_err(),
+        "Refused rename should be rolled back."
+    );
+
+    store.delete::<Pipeline>("double-style-transfer", "0.1.0", DeleteMode::Restrict)?;
+    store.rename::<Pod>("style-transfer", "0.67.0", &renamed)?;
+    assert_eq!(
+        store.load::<Pod>("style-transfer-v2", "0.67.0")?.hash,
+        pod.hash,
+        "Renamed annotation should keep the spec."
+    );
+    assert_eq!(
+        store
+            .load::<Pod>("style-transfer", "0.67.0")
+            .err()
+            .map(|error| error.kind()),
+        Some(ErrorKind::NoAnnotationFound),
+        "Old annotation should be gone."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_annotation_edits() -> Result<(), Box<dyn Error>> {
+    verify_annotation_edit_semantics(&*store_test(None)?)?;
+    verify_annotation_edit_semantics(&InMemoryStore::new())?;
+    #[cfg(feature = "sqlite")]
+    verify_annotation_edit_semantics(&SqliteStore::new(":memory:")?)?;
+    Ok(())
+}
+
+fn verify_tag_semantics(store: &impl Store) -> Result<(), Box<dyn Error>> {
+    let stable = pod_style()?;
+    let newest = pod_custom("style-transfer", "0.68.0", "tail -f /dev/null")?;
+    store.save(&stable)?;
+    store.save(&newest)?;
+
+    store.tag::<Pod>("style-transfer", "stable", "0.67.0")?;
+    assert_eq!(
+        store.load::<Pod>("style-transfer", "stable")?.hash,
+        stable.hash,
+        "Tag should resolve on load."
+    );
+    // a tag named `latest` takes precedence over the highest version
+    store.tag::<Pod>("style-transfer", "latest", "0.67.0")?;
+    assert_eq!(
+        store
+            .resolve_pod_ref(&PodRef::Annotation {
+                name: "style-transfer".to_owned(),
+                version: "latest".to_owned(),
+            })?
+            .hash,
+        stable.hash,
+        "Tag should resolve in pipeline references."
+    );
+
+    store.tag::<Pod>("style-transfer", "stable", "^0.68")?;
+    assert_eq!(
+        store.load::<Pod>("style-transfer", "stable")?.hash,
+        newest.hash,
+        "Retag should move the tag."
+    );
+    store.untag::<Pod>("style-transfer", "stable")?;
+    assert!(
+        store.load::<Pod>("style-transfer", "stable").is_err(),
+        "Removed tag shouldn't resolve."
+    );
+    assert_eq!(
+        store
+            .tag_history::<Pod>("style-transfer", "stable")?
+            .into_iter()
+            .map(|tag_move| tag_move.version)
+            .collect::<Vec<_>>(),
+        vec![
+            Some(Version::new(0, 67, 0)),
+            Some(Version::new(0, 68, 0)),
+            None
+        ],
+        "History should record every move."
+    );
+
+    for invalid in ["0.67", "^1", "not/a/tag"] {
+        assert_eq!(
+            store
+                .tag::<Pod>("style-transfer", invalid, "0.67.0")
+                .err()
+                .map(|error| error.kind()),
+            Some(ErrorKind::InvalidTag),
+            "Tag `{invalid}` should be rejected."
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn verify_tags() -> Result<(), Box<dyn Error>> {
+    verify_tag_semantics(&*store_test(None)?)?;
+    verify_tag_semantics(&InMemoryStore::new())?;
+    #[cfg(feature = "sqlite")]
+    verify_tag_semantics(&SqliteStore::new(":memory:")?)?;
+    Ok(())
+}
+
+/// Rewrite a store the way it looked before hashes were prefixed with their algorithm.
+fn strip_hash_prefixes(store: &LocalFileStore) -> Result<(), Box<dyn Error>> {
+    let mut paths = glob::glob(&format!(
+        "{}/**/*",
+        Pattern::escape(&store.directory.to_string_lossy())
+    ))?
+    .collect::<Result<Vec<_>, _>>()?;
+    // deepest first so that renaming a directory doesn't move paths yet to be renamed
+    paths.sort_by_key(|path| Reverse(path.components().count()));
+    for path in paths {
+        if path.is_file() {
+            fs::write(&path, fs::read_to_string(&path)?.replace("sha256:", ""))?;
+        }
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if file_name.starts_with("sha256-") {
+            fs::rename(
+                &path,
+                path.with_file_name(file_name.replacen("sha256-", "", 1)),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_migrates_legacy_hashes() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let pod = pod_style()?;
+    let pod_job = pod_job_style(&pod)?;
+    store.save(&pod)?;
+    store.save(&pod_job)?;
+    strip_hash_prefixes(&store)?;
+    let legacy_hash = pod.hash.replace("sha256:", "");
+    assert_eq!(
+        list_hashes::<Pod>(&*store)?,
+        vec![legacy_hash.clone()],
+        "Legacy store should still list."
+    );
+
+    let renamed = store.migrate_hashes(HashAlgorithm::Sha256)?;
+    assert_eq!(
+        renamed.get(&legacy_hash),
+        Some(&pod.hash),
+        "Pod digest should be kept under a prefix."
+    );
+    assert_eq!(
+        store
+            .load::<PodJob>("style-transfer-job", "0.1.0")?
+            .references(),
+        vec![Reference::Hash {
+            class: "pod".to_owned(),
+            hash: pod.hash,
+        }],
+        "Job should reference the migrated pod."
+    );
+    assert!(
+        store.verify::<Pod>()?.is_clean() && store.verify::<PodJob>()?.is_clean(),
+        "Migrated store should verify."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_migrates_spec_schema() -> Result<(), Box<dyn Error>> {
+    let store = store_test(None)?;
+    let pod = pod_style()?;
+    store.save(&pod)?;
+    // written before the header had a schema version
+    let pod_spec = store.make_spec_path("pod", &pod.hash);
+    fs::write(
+        &pod_spec,
+        fs::read_to_string(&pod_spec)?.replace("schema_version: 1\n", ""),
+    )?;
+
+    // schema 1 greeter, hashed back when its field was called `message`
+    let current = greeter("hello")?;
+    let legacy_hash = Multihash::of(r#"{"class":"greeter","message":"hello"}"#).to_string();
+    let legacy_spec = store.make_spec_path("greeter", &legacy_hash);
+    fs::create_dir_all(legacy_spec.parent().ok_or("Spec should have a parent.")?)?;
+    fs::write(
+        &legacy_spec,
+        "class: greeter\nschema_version: 1\nmessage: hello\n",
+    )?;
+    let legacy_annotation = store.make_annotation_path("greeter", &legacy_hash, "greeter", "0.1.0");
+    fs::create_dir_all(
+        legacy_annotation
+            .parent()
+            .ok_or("Annotation should have a parent.")?,
+    )?;
+    fs::write(
+        &legacy_annotation,
+        serde_yaml::to_string(&current.annotation)?,
+    )?;
+
+    assert!(
+        store.migrate_schema()?.is_empty(),
+        "Pod content is unchanged so its hash should be kept."
+    );
+    assert!(
+        fs::read_to_string(&pod_spec)?.contains("schema_version: 1\n"),
+        "Pod spec should be rewritten with a schema version."
+    );
+
+    let mut renamed = BTreeMap::new();
+    store.migrate_specs::<Greeter>(&mut renamed)?;
+    assert_eq!(
+        renamed,
+        BTreeMap::from([(legacy_hash, current.hash.clone())]),
+        "Greeter content changed so its hash should be remapped."
+    );
+    let migrated = store.load::<Greeter>("greeter", "0.1.0")?;
+    assert_eq!(
+        (migrated.hash, migrated.greeting),
+        (current.hash, current.greeting),
+        "Greeter should load at the current schema."
+    );
+    assert!(!legacy_spec.exists(), "Legacy spec should be removed.");
+    assert!(
+        store.verify::<Pod>()?.is_clean() && store.verify::<Greeter>()?.is_clean(),
+        "Migrated store should verify."
+    );
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_spec_formats() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let pod_job = pod_job_style(&pod)?;
+    for format in [SpecFormat::Json, SpecFormat::Toml] {
+        let directory = tempdir()?;
+        let store = LocalFileStore::new(directory.path()).with_spec_format(format);
+        store.save(&pod)?;
+        store.save(&pod_job)?;
+
+        let spec_file = store.make_spec_path("pod", &pod.hash);
+        assert!(
+            spec_file.ends_with(format!("spec.{}", format.extension())),
+            "Spec should be saved as {format:?}."
+        );
+        let spec = format.read(&fs::read_to_string(&spec_file)?)?;
+        assert_eq!(
+            spec.get("class").and_then(|class| class.as_str()),
+            Some("pod"),
+            "Spec should parse as {format:?}."
+        );
+        assert_eq!(
+            (
+                store.load::<Pod>("style-transfer", "0.67.0")?.hash,
+                store.load::<PodJob>("style-transfer-job", "0.1.0")?.hash
+            ),
+            (pod.hash.clone(), pod_job.hash.clone()),
+            "Hashes should not depend on the format."
+        );
+        assert!(
+            store.verify::<Pod>()?.is_clean() && store.verify::<PodJob>()?.is_clean(),
+            "Store in {format:?} should verify."
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn verify_local_store_reopens_in_spec_format() -> Result<(), Box<dyn Error>> {
+    let pod = pod_style()?;
+    let directory = tempdir()?;
+    let json_store = LocalFileStore::new(directory.path()).with_spec_format(SpecFormat::Json);
+    json_store.save(&pod)?;
+
+    let reopened = LocalFileStore::open(directory.path())?;
+    assert_eq!(
+        reopened.load::<Pod>("style-transfer", "0.67.0")?.hash,
+        pod.hash,
+        "Reopened store should load the pod in the format it was saved in."
+    );
+    let mut renamed = pod_style()?;
+    "style-transfer-copy".clone_into(&mut renamed.annotation.name);
+    reopened.save(&renamed)?;
+    assert!(
+        json_store.make_spec_path("pod", &pod.hash).exists()
+            && !reopened.make_spec_path("pod", &pod.hash).exists(),
+        "Another annotation of the spec shouldn't write it again in another format."
+    );
+    assert!(
+        reopened.verify::<Pod>()?.is_clean(),
+        "Specs should verify in the format they were saved in."
+    );
+    reopened.gc(&GcOptions {
+        dry_run: false,
+        grace_period: Duration::ZERO,
+    })?;
+    assert_eq!(
+        reopened.load_by_hash::<Pod>(&pod.hash)?.1.len(),
+        2,
+        "Garbage collection should keep specs in another format."
+    );
+    Ok(())
+}